@@ -0,0 +1,606 @@
+//! Command line of the launcher binary.
+
+use crate::error::{self, Coded, ErrorCode};
+use crate::exit_status;
+use crate::{
+    build_command, cmdline, command_in_environment, console, default_shell, edit, elevate, expand,
+    launch, launch_id, load_config, resolved_environment, run_check, same_env_name, shortcut,
+    snapshot, spawn_error, succeeded, terminal, trace, validate, verbose, watch, CommandConfig,
+    Config, EnvConfig, ARGS_PLACEHOLDER, LAUNCH_ID_VAR,
+};
+use anyhow::Context;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+fn parse_args() -> clap::ArgMatches {
+    clap::Command::new("launcher")
+        .args(&[
+            clap::Arg::new("config")
+                .long("config")
+                .short('c')
+                .value_parser(clap::value_parser!(PathBuf)),
+            clap::Arg::new("profile")
+                .long("profile")
+                .short('p')
+                .help("Profile of the config file to launch"),
+            clap::Arg::new("format")
+                .long("format")
+                .help("Syntax of the config file, by default chosen by its extension")
+                .value_parser(["toml", "json", "yaml"]),
+            clap::Arg::new("no-default-config")
+                .long("no-default-config")
+                .help("Don't look for a config file next to the launcher")
+                .conflicts_with("config")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("env")
+                .long("env")
+                .short('e')
+                .action(clap::ArgAction::Append),
+            clap::Arg::new("append-args")
+                .long("append-args")
+                .short('a')
+                .help("Append COMMAND words to the configured args instead of replacing the command")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("env-file")
+                .long("env-file")
+                .help("Dotenv file to load before [env]")
+                .value_parser(clap::value_parser!(PathBuf))
+                .action(clap::ArgAction::Append),
+            clap::Arg::new("detach")
+                .long("detach")
+                .short('d')
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("cwd")
+                .long("cwd")
+                .help("Directory to start the command in")
+                .value_parser(clap::value_parser!(PathBuf)),
+            clap::Arg::new("delay")
+                .long("delay")
+                .value_parser(humantime::parse_duration),
+            clap::Arg::new("error-format")
+                .long("error-format")
+                .value_parser(["human", "json"])
+                .default_value("human"),
+            clap::Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print the resolved command and environment without starting it")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("print-env")
+                .long("print-env")
+                .help("Print the resolved environment without starting the command")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log what the launcher does to stderr, or to the file ENV_LAUNCHER_LOG names")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("watch")
+                .long("watch")
+                .help("Restart the command when the config file changes, loading it again")
+                .action(clap::ArgAction::SetTrue),
+            clap::Arg::new("monitor")
+                .long("monitor")
+                .hide(true)
+                .action(clap::ArgAction::SetTrue),
+            // A single multi-valued positional, so words after the command are
+            // never mistaken for a subcommand.
+            clap::Arg::new("command")
+                .value_name("COMMAND")
+                .num_args(1..)
+                .action(clap::ArgAction::Append),
+        ])
+        .subcommand(
+            clap::Command::new("set")
+                .about("Persist a setting into the config file")
+                .subcommand_required(true)
+                .subcommands([
+                    clap::Command::new("env").arg(
+                        clap::Arg::new("assignment")
+                            .value_name("NAME=VALUE")
+                            .required(true)
+                            .action(clap::ArgAction::Append),
+                    ),
+                    clap::Command::new("command").arg(clap::Arg::new("command").required(true)),
+                    clap::Command::new("args").arg(
+                        clap::Arg::new("arg")
+                            .allow_hyphen_values(true)
+                            .action(clap::ArgAction::Append),
+                    ),
+                ]),
+        )
+        .subcommand(
+            clap::Command::new("validate")
+                .about("Check the active config file for unknown keys and other mistakes"),
+        )
+        .subcommand(
+            clap::Command::new("edit")
+                .about("Open the active config file in %EDITOR%")
+                .arg(
+                    clap::Arg::new("validate")
+                        .long("validate")
+                        .help("Check that the config still parses once the editor exits")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("import-shortcut")
+                .about("Convert a .lnk shortcut into a launcher config")
+                .args(&[
+                    clap::Arg::new("shortcut")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                    clap::Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ]),
+        )
+        .subcommand(
+            clap::Command::new("export-shortcut")
+                .about("Create a .lnk shortcut running the launcher with this config")
+                .arg(
+                    clap::Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("schema").about("Print a JSON Schema of the config file"),
+        )
+        .subcommand(
+            clap::Command::new("export")
+                .about("Print the environment changes for use in another tool")
+                .arg(
+                    clap::Arg::new("format")
+                        .long("format")
+                        .value_parser(["vscode", "vscode-task"])
+                        .default_value("vscode"),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("wt-profile")
+                .about("Create a Windows Terminal profile fragment running the launcher with this config")
+                .arg(
+                    clap::Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("check")
+                .about("Compare the resolved command and environment with a snapshot file")
+                .args(&[
+                    clap::Arg::new("against")
+                        .long("against")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                    clap::Arg::new("update")
+                        .long("update")
+                        .help("Write the snapshot file instead of comparing")
+                        .action(clap::ArgAction::SetTrue),
+                ]),
+        )
+        .subcommand(
+            clap::Command::new("shell")
+                .about("Open an interactive shell with the configured environment")
+                .arg(
+                    clap::Arg::new("shell")
+                        .long("shell")
+                        .help("Shell to run instead of %COMSPEC% ($SHELL on Unix)")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("test")
+                .about("Run the [check] command with the configured environment"),
+        )
+        .get_matches()
+}
+
+fn override_config_with_args(config: &mut Config, args: &clap::ArgMatches) -> anyhow::Result<()> {
+    let words: Vec<&String> = args
+        .get_many::<String>("command")
+        .into_iter()
+        .flatten()
+        .collect();
+    let numbered = fill_numbered_args(config, &words)?;
+    let literal: Vec<String> = words
+        .iter()
+        .map(|word| literal_word(config, word))
+        .collect();
+    if let Some(index) = config.args.iter().position(|arg| arg == ARGS_PLACEHOLDER) {
+        // The config fixes the command, CLI words become extra arguments.
+        config.args.splice(index..=index, literal);
+    } else if numbered {
+        // The words went into the `{N}` placeholders.
+    } else if args.get_flag("append-args") {
+        config.args.extend(literal);
+    } else if let Some((command, args)) = words.split_first() {
+        config.command = CommandConfig::Path(PathBuf::from(expand::escape(command)));
+        config.args = args.iter().map(|arg| expand::escape(arg)).collect();
+    }
+    if let Some(envs) = args.get_many::<String>("env") {
+        for env in envs {
+            if let Some((env_name, env_value)) = env.split_once('=') {
+                config.env.insert(
+                    env_name.to_string(),
+                    EnvConfig::Simple(env_value.to_string()),
+                );
+            }
+        }
+    }
+    if args.get_flag("detach") {
+        config.detach = true;
+    }
+    if let Some(delay) = args.get_one::<Duration>("delay") {
+        config.delay = Some(*delay);
+    }
+    for env_file in args.get_many::<PathBuf>("env-file").into_iter().flatten() {
+        config
+            .env_files
+            .push(std::path::absolute(env_file).unwrap_or_else(|_| env_file.clone()));
+    }
+    if let Some(cwd) = args.get_one::<PathBuf>("cwd") {
+        // Relative to where the launcher runs, not to the config file.
+        config.cwd = Some(std::path::absolute(cwd).unwrap_or_else(|_| cwd.clone()));
+    }
+    Ok(())
+}
+
+/// A word from the command line as it goes into the configured `args`:
+/// escaped, since the expansion of `${NAME}` and `%NAME%` is only for what
+/// the config says. Packaged apps get their arguments unexpanded anyway.
+fn literal_word(config: &Config, word: &str) -> String {
+    match config.command {
+        CommandConfig::App { .. } => word.to_string(),
+        CommandConfig::Path(_) => expand::escape(word),
+    }
+}
+
+/// Replaces `{1}`, `{2}`, ... in the configured `args` with the command line
+/// words at that position. Returns whether there were any.
+fn fill_numbered_args(config: &mut Config, words: &[&String]) -> anyhow::Result<bool> {
+    let placeholder = regex::Regex::new(r"\{([1-9][0-9]*)\}").unwrap();
+    if !config.args.iter().any(|arg| placeholder.is_match(arg)) {
+        return Ok(false);
+    }
+    let literal: Vec<String> = words
+        .iter()
+        .map(|word| literal_word(config, word))
+        .collect();
+    let mut used = 0;
+    for arg in &mut config.args {
+        let mut missing = None;
+        let filled = placeholder.replace_all(arg, |captures: &regex::Captures| {
+            let position = captures[1].parse::<usize>().unwrap_or(usize::MAX);
+            used = used.max(position);
+            match literal.get(position - 1) {
+                Some(word) => word.clone(),
+                None => {
+                    missing.get_or_insert_with(|| captures[0].to_string());
+                    String::new()
+                }
+            }
+        });
+        if let Some(missing) = missing {
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("no command line argument for {} in args", missing),
+            )
+            .into());
+        }
+        *arg = filled.into_owned();
+    }
+    // With `{args}` as well, every word is passed on anyway.
+    if words.len() > used && !config.args.iter().any(|arg| arg == ARGS_PLACEHOLDER) {
+        verbose::log(format_args!(
+            "command line arguments not used by args: {}",
+            cmdline::join(words[used..].iter().map(|word| word.as_str()))
+        ));
+    }
+    Ok(true)
+}
+
+/// Name and arguments for launching the launcher again the way it was invoked,
+/// for shortcuts and terminal profiles.
+fn launcher_args(
+    args: &clap::ArgMatches,
+    config: &Config,
+) -> anyhow::Result<(String, Vec<String>)> {
+    let config_path = match args.get_one::<PathBuf>("config") {
+        Some(path) => Some(std::path::absolute(path).context("get config path")?),
+        None => None,
+    };
+    let mut launcher_args = Vec::new();
+    if let Some(path) = &config_path {
+        launcher_args.extend(["-c".to_string(), path.to_string_lossy().into_owned()]);
+    }
+    if args.get_flag("no-default-config") {
+        launcher_args.push("--no-default-config".to_string());
+    }
+    if let Some(profile) = args.get_one::<String>("profile") {
+        launcher_args.extend(["--profile".to_string(), profile.clone()]);
+    }
+    if let Some(format) = args.get_one::<String>("format") {
+        launcher_args.extend(["--format".to_string(), format.clone()]);
+    }
+    for env in args.get_many::<String>("env").into_iter().flatten() {
+        launcher_args.extend(["-e".to_string(), env.clone()]);
+    }
+    if args.get_flag("detach") {
+        launcher_args.push("-d".to_string());
+    }
+    if args.get_flag("append-args") {
+        launcher_args.push("--append-args".to_string());
+    }
+    if args.get_flag("watch") {
+        launcher_args.push("--watch".to_string());
+    }
+    if args.get_flag("verbose") {
+        launcher_args.push("-v".to_string());
+    }
+    if let Some(delay) = args.get_one::<Duration>("delay") {
+        launcher_args.extend([
+            "--delay".to_string(),
+            humantime::format_duration(*delay).to_string(),
+        ]);
+    }
+    let name = match &config_path {
+        Some(path) => path.file_stem(),
+        None => config.command.as_path().file_stem(),
+    };
+    Ok((
+        name.unwrap_or_default().to_string_lossy().into_owned(),
+        launcher_args,
+    ))
+}
+
+fn default_config_path() -> anyhow::Result<PathBuf> {
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    Ok(launcher_path.with_extension("toml"))
+}
+
+fn run(args: &clap::ArgMatches) -> anyhow::Result<()> {
+    if args.get_flag("verbose") {
+        verbose::enable();
+    }
+    let active_config_path = || match args.get_one::<PathBuf>("config") {
+        Some(path) => Ok(path.clone()),
+        None if args.get_flag("no-default-config") => Err(Coded::new(
+            ErrorCode::ConfigNotFound,
+            "no config file given with --no-default-config",
+        )
+        .into()),
+        None => default_config_path(),
+    };
+    let format = args.get_one::<String>("format").map(String::as_str);
+    match args.subcommand() {
+        Some(("set", set_args)) => return edit::set(&active_config_path()?, set_args),
+        Some(("edit", edit_args)) => {
+            let config_path = active_config_path()?;
+            edit::open_in_editor(&config_path)?;
+            if edit_args.get_flag("validate") {
+                validate::validate(&config_path, None, format)?;
+            }
+            return Ok(());
+        }
+        Some(("validate", _)) => {
+            return validate::validate(
+                &active_config_path()?,
+                args.get_one::<String>("profile").map(String::as_str),
+                format,
+            )
+        }
+        Some(("import-shortcut", import_args)) => {
+            let shortcut_path = import_args.get_one::<PathBuf>("shortcut").unwrap();
+            let config = shortcut::import(shortcut_path)?.to_string();
+            match import_args.get_one::<PathBuf>("out") {
+                Some(out) => std::fs::write(out, config)
+                    .with_context(|| format!("write config file {}", out.display()))?,
+                None => print!("{}", config),
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    let config_path = match args.get_one::<PathBuf>("config") {
+        Some(path) => Some(path.clone()),
+        None if args.get_flag("no-default-config") => None,
+        None => {
+            let path = default_config_path()?;
+            let found = path.exists();
+            verbose::log(format_args!(
+                "{} config file next to the launcher at {}",
+                if found { "found" } else { "no" },
+                path.display()
+            ));
+            Some(path).filter(|_| found)
+        }
+    };
+    let resolve_config = || -> anyhow::Result<Config> {
+        let mut config = match &config_path {
+            Some(path) => load_config(
+                path,
+                args.get_one::<String>("profile").map(String::as_str),
+                format,
+            )?,
+            None if args.contains_id("profile") => {
+                return Err(
+                    Coded::new(ErrorCode::ConfigNotFound, "no config file for --profile").into(),
+                )
+            }
+            None => Config::default(),
+        };
+        override_config_with_args(&mut config, args)?;
+        config.watch_config = args.get_flag("watch");
+        Ok(config)
+    };
+    let mut config = resolve_config()?;
+
+    // These subcommands only need the environment, not the command.
+    let needs_command = !matches!(args.subcommand_name(), Some("shell" | "test" | "export"))
+        && config.process_configs.is_empty();
+    if needs_command && config.command.as_path().as_os_str().is_empty() {
+        return Err(Coded::new(ErrorCode::CommandNotSpecified, "command not specified").into());
+    }
+    trace::config_resolved(config_path.as_deref(), config.command.as_path());
+
+    match args.subcommand() {
+        Some(("export-shortcut", export_args)) => {
+            let (name, launcher_args) = launcher_args(args, &config)?;
+            let shortcut_path = shortcut::export(
+                export_args.get_one::<PathBuf>("out").unwrap(),
+                &name,
+                &launcher_args,
+                Some(config.command.as_path()).filter(|command| command.is_file()),
+                config.cwd.as_deref(),
+            )?;
+            eprintln!("created {}", shortcut_path.display());
+            return Ok(());
+        }
+        Some(("schema", _)) => {
+            let schema = schemars::schema_for!(Config);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            return Ok(());
+        }
+        Some(("export", export_args)) => {
+            let command = build_command(&config)?;
+            // A launch id pasted into settings would be shared by every
+            // terminal started from them.
+            let env: serde_json::Map<String, serde_json::Value> = command
+                .get_envs()
+                .filter(|(name, _)| !same_env_name(name, LAUNCH_ID_VAR.as_ref()))
+                .map(|(name, value)| {
+                    (
+                        name.to_string_lossy().into_owned(),
+                        value.map_or(serde_json::Value::Null, |value| {
+                            value.to_string_lossy().into()
+                        }),
+                    )
+                })
+                .collect();
+            let settings = match export_args.get_one::<String>("format").unwrap().as_str() {
+                "vscode-task" => serde_json::json!({ "options": { "env": env } }),
+                _ => serde_json::json!({ "terminal.integrated.env.windows": env }),
+            };
+            println!("{}", serde_json::to_string_pretty(&settings)?);
+            return Ok(());
+        }
+        Some(("wt-profile", profile_args)) => {
+            let (name, launcher_args) = launcher_args(args, &config)?;
+            let fragment_path = terminal::export(
+                profile_args.get_one::<PathBuf>("out").unwrap(),
+                &name,
+                &launcher_args,
+                Some(config.command.as_path()).filter(|command| command.is_file()),
+                config.cwd.as_deref(),
+            )?;
+            eprintln!("created {}", fragment_path.display());
+            return Ok(());
+        }
+        Some(("test", _)) => return run_check(&config),
+        Some(("shell", shell_args)) => {
+            let shell = match shell_args.get_one::<PathBuf>("shell") {
+                Some(shell) => shell.clone(),
+                None => default_shell(),
+            };
+            let status = command_in_environment(&config, &shell, &[])?
+                .status()
+                .map_err(|error| spawn_error(error, &shell))?;
+            if !status.success() {
+                exit_status::exit(status);
+            }
+            return Ok(());
+        }
+        Some(("check", check_args)) => {
+            let expected_path = check_args.get_one::<PathBuf>("against").unwrap();
+            let actual = snapshot::Snapshot::new(&build_command(&config)?, launch_id());
+            if check_args.get_flag("update") {
+                actual.save(expected_path)?;
+                eprintln!("updated {}", expected_path.display());
+                return Ok(());
+            }
+            let differences = actual.differences(&snapshot::Snapshot::load(expected_path)?);
+            if !differences.is_empty() {
+                for difference in &differences {
+                    eprintln!("{}", difference);
+                }
+                anyhow::bail!(
+                    "resolved config differs from {} in {} places",
+                    expected_path.display(),
+                    differences.len()
+                );
+            }
+            eprintln!("resolved config matches {}", expected_path.display());
+            return Ok(());
+        }
+        Some((subcommand, _)) => unreachable!("unknown subcommand {}", subcommand),
+        None => {}
+    }
+
+    if args.get_flag("dry-run") || args.get_flag("print-env") {
+        let command = build_command(&config)?;
+        if args.get_flag("dry-run") {
+            println!(
+                "command: {}",
+                cmdline::join(
+                    std::iter::once(command.get_program())
+                        .chain(command.get_args())
+                        .map(OsStr::to_string_lossy)
+                )
+            );
+            if let Some(cwd) = command.get_current_dir() {
+                println!("cwd: {}", cwd.display());
+            }
+            println!("env:");
+        }
+        for (name, value) in resolved_environment(&config, &command) {
+            println!("{}={}", name.to_string_lossy(), value.to_string_lossy());
+        }
+        return Ok(());
+    }
+
+    let status = loop {
+        match launch(&config, args.get_flag("monitor")) {
+            Err(error) if error.is::<watch::ConfigChanged>() => {
+                eprintln!("{}, loading it again", error);
+                // Keep running the old config until the file is fixed.
+                match resolve_config() {
+                    Ok(reloaded) => config = reloaded,
+                    Err(error) => eprintln!("warning: {:?}", error),
+                }
+            }
+            result => break result?,
+        }
+    };
+    if let Some(status) = status {
+        if !succeeded(&config, status) {
+            exit_status::exit(status);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn main() {
+    if cfg!(feature = "gui") {
+        console::attach_parent_console();
+    }
+    trace::register();
+    let args = parse_args();
+    if let Err(error) = run(&args) {
+        if let Some(elevate::Relaunched(code)) = error.downcast_ref() {
+            process::exit(code.unwrap_or(0) as i32);
+        }
+        let json = args.get_one::<String>("error-format").map(String::as_str) == Some("json");
+        error::report(&error, json);
+        process::exit(error::code(&error).exit_code());
+    }
+}
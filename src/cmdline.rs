@@ -0,0 +1,189 @@
+/// Splits a command line into arguments following the Microsoft C runtime
+/// rules (the ones `CommandLineToArgvW` applies after the program name).
+pub fn split(command_line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = command_line.chars().peekable();
+    loop {
+        while chars.next_if(|c| matches!(c, ' ' | '\t')).is_some() {}
+        if chars.peek().is_none() {
+            return args;
+        }
+        let mut arg = String::new();
+        let mut in_quotes = false;
+        let mut backslashes = 0;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => backslashes += 1,
+                '"' => {
+                    arg.extend(std::iter::repeat_n('\\', backslashes / 2));
+                    // An escaped quote, or `""` inside quotes, is a literal quote.
+                    if backslashes % 2 == 1 || (in_quotes && chars.next_if_eq(&'"').is_some()) {
+                        arg.push('"');
+                    } else {
+                        in_quotes = !in_quotes;
+                    }
+                    backslashes = 0;
+                }
+                ' ' | '\t' if !in_quotes => break,
+                _ => {
+                    arg.extend(std::iter::repeat_n('\\', backslashes));
+                    arg.push(c);
+                    backslashes = 0;
+                }
+            }
+        }
+        arg.extend(std::iter::repeat_n('\\', backslashes));
+        args.push(arg);
+    }
+}
+
+/// Quotes an argument so that [`split`] turns it back into the same string.
+pub fn quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '"']) {
+        return arg.to_string();
+    }
+    quote_always(arg)
+}
+
+/// Like [`quote`], but also quotes arguments that don't need it, such as
+/// ones with characters that cmd.exe would interpret outside of quotes.
+pub fn quote_always(arg: &str) -> String {
+    let mut quoted = String::from('"');
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.extend(std::iter::repeat_n('\\', backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.extend(std::iter::repeat_n('\\', backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+pub fn join<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    args.into_iter()
+        .map(|arg| quote(arg.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Like [`join`], for arguments that need not be valid Unicode, as the
+/// UTF-16 command line Windows takes.
+#[cfg(windows)]
+pub fn join_wide<I, S>(args: I) -> Vec<u16>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    use std::os::windows::ffi::OsStrExt;
+    let [space, tab, quote, backslash] = [' ', '\t', '"', '\\'].map(|c| c as u16);
+    let mut line = Vec::new();
+    for (index, arg) in args.into_iter().enumerate() {
+        if index > 0 {
+            line.push(space);
+        }
+        let arg: Vec<u16> = arg.as_ref().encode_wide().collect();
+        if !arg.is_empty() && !arg.iter().any(|c| [space, tab, quote].contains(c)) {
+            line.extend(arg);
+            continue;
+        }
+        line.push(quote);
+        let mut backslashes = 0;
+        for c in arg {
+            if c == backslash {
+                backslashes += 1;
+                continue;
+            }
+            let escaped = if c == quote {
+                backslashes * 2 + 1
+            } else {
+                backslashes
+            };
+            line.extend(std::iter::repeat_n(backslash, escaped));
+            line.push(c);
+            backslashes = 0;
+        }
+        line.extend(std::iter::repeat_n(backslash, backslashes * 2));
+        line.push(quote);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join, split};
+
+    #[test]
+    fn splits_on_unquoted_whitespace() {
+        assert_eq!(split("  a\tb   c "), ["a", "b", "c"]);
+        assert_eq!(
+            split(r#"--name "two words" x"#),
+            ["--name", "two words", "x"]
+        );
+        assert_eq!(split(r#"pre"quoted part"post"#), ["prequoted partpost"]);
+        assert!(split(" \t ").is_empty());
+    }
+
+    #[test]
+    fn keeps_empty_quoted_arguments() {
+        assert_eq!(split(r#"a "" b"#), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn treats_backslashes_literally_unless_before_a_quote() {
+        assert_eq!(
+            split(r"C:\Program\ Files\ a"),
+            [r"C:\Program\", r"Files\", "a"]
+        );
+        assert_eq!(split(r"a\\b"), [r"a\\b"]);
+        // 2n backslashes before a quote are n backslashes, and the quote
+        // delimits; 2n + 1 are n backslashes and a literal quote.
+        assert_eq!(split(r#""dir\\" x"#), [r"dir\", "x"]);
+        assert_eq!(split(r#"a\"b"#), [r#"a"b"#]);
+        assert_eq!(split(r#"a\\\"b"#), [r#"a\"b"#]);
+        assert_eq!(split(r#"a\\\\"b c""#), [r"a\\b c"]);
+        assert_eq!(split(r"trailing\\"), [r"trailing\\"]);
+    }
+
+    #[test]
+    fn reads_doubled_quotes_inside_quotes_as_a_quote() {
+        assert_eq!(split(r#""a""b""#), [r#"a"b"#]);
+        assert_eq!(split(r#""say ""hi""" x"#), [r#"say "hi""#, "x"]);
+    }
+
+    #[test]
+    fn join_is_undone_by_split() {
+        let args = [
+            "plain",
+            "",
+            "two words",
+            r#"quote " inside"#,
+            r"C:\dir with spaces\",
+            r#"back\"slash"#,
+            "tab\there",
+        ];
+        assert_eq!(split(&join(args)), args);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn join_wide_matches_join() {
+        let args = ["plain", "", "two words", r#"a\"b"#, r"C:\dir with spaces\"];
+        let wide: Vec<u16> = join(args).encode_utf16().collect();
+        assert_eq!(super::join_wide(args), wide);
+    }
+}
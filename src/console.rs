@@ -0,0 +1,99 @@
+#[cfg(windows)]
+use windows::core::w;
+#[cfg(windows)]
+use windows::Win32::Foundation::{GENERIC_READ, GENERIC_WRITE};
+#[cfg(windows)]
+use windows::Win32::Globalization::CP_UTF8;
+#[cfg(windows)]
+use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FILE_FLAGS_AND_ATTRIBUTES, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
+#[cfg(windows)]
+use windows::Win32::System::Console::{
+    AttachConsole, GetConsoleCP, GetConsoleOutputCP, GetStdHandle, SetConsoleCP,
+    SetConsoleOutputCP, SetStdHandle, ATTACH_PARENT_PROCESS, STD_ERROR_HANDLE, STD_HANDLE,
+    STD_OUTPUT_HANDLE,
+};
+
+/// Attaches the GUI-subsystem launcher to the console of its parent, if it
+/// has one, so command line usage still prints output and errors.
+#[cfg(windows)]
+pub fn attach_parent_console() {
+    unsafe {
+        if AttachConsole(ATTACH_PARENT_PROCESS).is_err() {
+            return;
+        }
+        // Handles redirected by the parent are inherited and kept as they are.
+        for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+            if !GetStdHandle(std_handle).unwrap_or_default().is_invalid() {
+                continue;
+            }
+            set_console_output(std_handle);
+        }
+    }
+}
+
+/// Unix programs always run with the streams of their parent.
+#[cfg(not(windows))]
+pub fn attach_parent_console() {}
+
+#[cfg(windows)]
+unsafe fn set_console_output(std_handle: STD_HANDLE) {
+    let console = unsafe {
+        CreateFileW(
+            w!("CONOUT$"),
+            (GENERIC_READ | GENERIC_WRITE).0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            None,
+        )
+    };
+    if let Ok(console) = console {
+        let _ = unsafe { SetStdHandle(std_handle, console) };
+    }
+}
+
+/// Switches the shared console to the UTF-8 code page, restoring the previous
+/// code pages when dropped. Unix terminals need no switching.
+pub struct Utf8CodePage {
+    #[cfg(windows)]
+    input: u32,
+    #[cfg(windows)]
+    output: u32,
+}
+
+impl Utf8CodePage {
+    #[cfg(windows)]
+    pub fn set() -> Self {
+        unsafe {
+            let code_page = Utf8CodePage {
+                input: GetConsoleCP(),
+                output: GetConsoleOutputCP(),
+            };
+            let _ = SetConsoleCP(CP_UTF8);
+            let _ = SetConsoleOutputCP(CP_UTF8);
+            code_page
+        }
+    }
+
+    #[cfg(not(windows))]
+    pub fn set() -> Self {
+        Utf8CodePage {}
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Utf8CodePage {
+    fn drop(&mut self) {
+        // Without a console there is nothing to restore.
+        if self.input == 0 || self.output == 0 {
+            return;
+        }
+        unsafe {
+            let _ = SetConsoleCP(self.input);
+            let _ = SetConsoleOutputCP(self.output);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+#[cfg(any(windows, feature = "secret-store"))]
+use anyhow::Context;
+#[cfg(windows)]
+use windows::core::HSTRING;
+#[cfg(windows)]
+use windows::Win32::Security::Credentials::{CredFree, CredReadW, CREDENTIALW, CRED_TYPE_GENERIC};
+
+/// Reads the password of the generic credential `name` from the Credential
+/// Manager.
+#[cfg(windows)]
+pub fn read(name: &str) -> anyhow::Result<String> {
+    let mut credential: *mut CREDENTIALW = std::ptr::null_mut();
+    unsafe {
+        CredReadW(
+            &HSTRING::from(name),
+            CRED_TYPE_GENERIC,
+            None,
+            &mut credential,
+        )
+        .with_context(|| format!("read credential {}", name))?;
+        // An empty password may come without a blob pointer at all.
+        let blob: &[u8] = if (*credential).CredentialBlob.is_null() {
+            &[]
+        } else {
+            std::slice::from_raw_parts(
+                (*credential).CredentialBlob,
+                (*credential).CredentialBlobSize as usize,
+            )
+        };
+        // cmdkey and the Credential Manager UI store UTF-16, other tools UTF-8.
+        let utf16 = blob.len().is_multiple_of(2) && blob.iter().skip(1).step_by(2).any(|&c| c == 0);
+        let value = if utf16 {
+            let wide: Vec<u16> = blob
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&wide)
+        } else {
+            String::from_utf8_lossy(blob).into_owned()
+        };
+        CredFree(credential as *const _);
+        Ok(value)
+    }
+}
+
+/// Reads the password stored for the service `name` and the current user
+/// from the Keychain or the Secret Service.
+#[cfg(all(unix, feature = "secret-store"))]
+pub fn read(name: &str) -> anyhow::Result<String> {
+    let user = std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .context("USER not set")?;
+    keyring::Entry::new(name, &user)
+        .and_then(|entry| entry.get_password())
+        .with_context(|| format!("read credential {}", name))
+}
+
+#[cfg(all(unix, not(feature = "secret-store")))]
+pub fn read(_name: &str) -> anyhow::Result<String> {
+    anyhow::bail!("credential needs the launcher built with the secret-store feature")
+}
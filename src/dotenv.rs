@@ -0,0 +1,174 @@
+use crate::error::{Coded, ErrorCode};
+use std::io;
+use std::path::Path;
+
+/// A variable of a dotenv file.
+#[derive(Debug, PartialEq)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+    /// The value was single quoted, so references in it are not expanded.
+    pub literal: bool,
+}
+
+/// Reads the variables of a dotenv file in file order.
+///
+/// Supports `NAME=value` lines with an optional `export ` prefix, `#`
+/// comments, and single or double quoted values; single quoted values are
+/// taken literally, double quoted values may contain `\n`, `\t`, `\"` and `\\` escapes.
+pub fn load(path: &Path) -> anyhow::Result<Vec<Variable>> {
+    let content = std::fs::read_to_string(path).map_err(|error| {
+        let code = match error.kind() {
+            io::ErrorKind::NotFound => ErrorCode::ConfigNotFound,
+            _ => ErrorCode::ConfigInvalid,
+        };
+        anyhow::Error::new(error).context(Coded::new(
+            code,
+            format!("read env file {}", path.display()),
+        ))
+    })?;
+    parse(&content, path)
+}
+
+/// Parses the content of the dotenv file at `path`, which only names the
+/// file in errors.
+fn parse(content: &str, path: &Path) -> anyhow::Result<Vec<Variable>> {
+    let mut variables = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("{}:{}: expected NAME=value", path.display(), index + 1),
+            )
+            .into());
+        };
+        let value = value.trim();
+        variables.push(Variable {
+            name: name.trim().to_string(),
+            value: parse_value(value),
+            literal: value.starts_with('\''),
+        });
+    }
+    Ok(variables)
+}
+
+fn parse_value(value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('\'') {
+        return quoted.split('\'').next().unwrap_or_default().to_string();
+    }
+    if let Some(quoted) = value.strip_prefix('"') {
+        let mut parsed = String::new();
+        let mut chars = quoted.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => parsed.push('\n'),
+                    Some('t') => parsed.push('\t'),
+                    Some(escaped) => parsed.push(escaped),
+                    None => parsed.push('\\'),
+                },
+                c => parsed.push(c),
+            }
+        }
+        return parsed;
+    }
+    // An unquoted value ends at a comment.
+    match value.find(" #") {
+        Some(comment) => value[..comment].trim_end().to_string(),
+        None => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Variable};
+    use std::path::Path;
+
+    fn variables(content: &str) -> Vec<Variable> {
+        parse(content, Path::new(".env")).unwrap()
+    }
+
+    fn pair(name: &str, value: &str) -> Variable {
+        Variable {
+            name: name.to_string(),
+            value: value.to_string(),
+            literal: false,
+        }
+    }
+
+    fn literal(name: &str, value: &str) -> Variable {
+        Variable {
+            literal: true,
+            ..pair(name, value)
+        }
+    }
+
+    #[test]
+    fn reads_variables_in_file_order() {
+        assert_eq!(
+            variables("B=2\nA = 1\r\n  C=three  \n"),
+            [pair("B", "2"), pair("A", "1"), pair("C", "three")]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        assert_eq!(
+            variables("# comment\n\n   \nA=1 # trailing comment\nB=a#b\n"),
+            [pair("A", "1"), pair("B", "a#b")]
+        );
+    }
+
+    #[test]
+    fn strips_the_export_prefix() {
+        assert_eq!(
+            variables("export A=1\nexporter=2\n"),
+            [pair("A", "1"), pair("exporter", "2")]
+        );
+    }
+
+    #[test]
+    fn reads_quoted_values() {
+        assert_eq!(
+            variables("A='single # kept \\n'\nB=\"double # kept\" # comment\nC=\"\"\nD=a=b\n"),
+            [
+                literal("A", "single # kept \\n"),
+                pair("B", "double # kept"),
+                pair("C", ""),
+                pair("D", "a=b"),
+            ]
+        );
+    }
+
+    #[test]
+    fn marks_only_single_quoted_values_literal() {
+        assert_eq!(
+            variables("A='${HOME}'\nB=\"${HOME}\"\nC=${HOME}\n"),
+            [
+                literal("A", "${HOME}"),
+                pair("B", "${HOME}"),
+                pair("C", "${HOME}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unescapes_double_quoted_values() {
+        assert_eq!(
+            variables(r#"A="line\nnext\ttab \"quoted\" back\\slash""#),
+            [pair("A", "line\nnext\ttab \"quoted\" back\\slash")]
+        );
+    }
+
+    #[test]
+    fn rejects_lines_without_a_value() {
+        let error = parse("A=1\nnot a variable\n", Path::new(".env")).unwrap_err();
+        assert_eq!(error.to_string(), ".env:2: expected NAME=value");
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+use toml_edit::{table, value, Array, DocumentMut};
+
+fn load_document(path: &Path) -> anyhow::Result<DocumentMut> {
+    if !path.exists() {
+        return Ok(DocumentMut::new());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("read config file {}", path.display()))?;
+    content
+        .parse()
+        .with_context(|| format!("parse config file {}", path.display()))
+}
+
+/// Applies a `set` subcommand to the config file, keeping its comments and
+/// formatting intact.
+pub fn set(path: &Path, args: &clap::ArgMatches) -> anyhow::Result<()> {
+    let mut document = load_document(path)?;
+    match args.subcommand() {
+        Some(("env", args)) => {
+            let env = document.entry("env").or_insert(table());
+            for assignment in args.get_many::<String>("assignment").into_iter().flatten() {
+                let (env_name, env_value) = assignment
+                    .split_once('=')
+                    .with_context(|| format!("expect NAME=VALUE, got {}", assignment))?;
+                env[env_name] = value(env_value);
+            }
+        }
+        Some(("command", args)) => {
+            let command = args.get_one::<String>("command").unwrap();
+            document["command"] = value(command.as_str());
+        }
+        Some(("args", args)) => {
+            let command_args: Array = args
+                .get_many::<String>("arg")
+                .into_iter()
+                .flatten()
+                .collect();
+            document["args"] = value(command_args);
+        }
+        _ => unreachable!("set requires a subcommand"),
+    }
+    std::fs::write(path, document.to_string())
+        .with_context(|| format!("write config file {}", path.display()))
+}
+
+#[cfg(windows)]
+const DEFAULT_EDITOR: &str = "notepad.exe";
+#[cfg(unix)]
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Opens the config file in `%VISUAL%`/`%EDITOR%`, falling back to notepad
+/// (vi on Unix), and waits for the editor to exit.
+pub fn open_in_editor(path: &Path) -> anyhow::Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    let mut editor_args = editor.split_whitespace();
+    let program = editor_args.next().context("empty editor command")?;
+    let status = Command::new(program)
+        .args(editor_args)
+        .arg(path)
+        .status()
+        .with_context(|| format!("run editor {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("editor {} exited with {}", editor, status);
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+#[cfg(windows)]
+use crate::cmdline;
+#[cfg(not(windows))]
+use crate::exit_status;
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fmt;
+use std::io;
+#[cfg(windows)]
+use windows::core::{w, HSTRING, PCWSTR};
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+#[cfg(windows)]
+use windows::Win32::Security::{GetTokenInformation, TokenElevation, TOKEN_ELEVATION, TOKEN_QUERY};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, GetExitCodeProcess, OpenProcessToken, WaitForSingleObject, INFINITE,
+};
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{ShellExecuteExW, SEE_MASK_NOCLOSEPROCESS, SHELLEXECUTEINFOW};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::SW_SHOWNORMAL;
+
+#[cfg(windows)]
+const ERROR_ACCESS_DENIED: i32 = 5;
+#[cfg(windows)]
+const ERROR_ELEVATION_REQUIRED: i32 = 740;
+
+/// `true` to always run the command elevated, or `"if_needed"`.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Elevate {
+    Always(bool),
+    When(ElevateWhen),
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ElevateWhen {
+    /// Relaunch elevated only when the command cannot be started otherwise.
+    IfNeeded,
+}
+
+impl Elevate {
+    pub fn always(self) -> bool {
+        self == Elevate::Always(true)
+    }
+
+    pub fn if_needed(self) -> bool {
+        self == Elevate::When(ElevateWhen::IfNeeded)
+    }
+}
+
+/// Whether a spawn failed because the command needs more rights than the
+/// launcher has.
+#[cfg(windows)]
+pub fn required(error: &io::Error) -> bool {
+    matches!(
+        error.raw_os_error(),
+        Some(ERROR_ELEVATION_REQUIRED | ERROR_ACCESS_DENIED)
+    ) && !is_elevated()
+}
+
+#[cfg(not(windows))]
+pub fn required(error: &io::Error) -> bool {
+    error.kind() == io::ErrorKind::PermissionDenied && !is_elevated()
+}
+
+#[cfg(windows)]
+pub fn is_elevated() -> bool {
+    unsafe {
+        let mut token = HANDLE::default();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token).is_err() {
+            return false;
+        }
+        let mut elevation = TOKEN_ELEVATION::default();
+        let mut len = 0;
+        let result = GetTokenInformation(
+            token,
+            TokenElevation,
+            Some(&mut elevation as *mut _ as *mut _),
+            size_of::<TOKEN_ELEVATION>() as u32,
+            &mut len,
+        );
+        let _ = CloseHandle(token);
+        result.is_ok() && elevation.TokenIsElevated != 0
+    }
+}
+
+#[cfg(not(windows))]
+pub fn is_elevated() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+/// The launch was handed to an elevated copy of the launcher, which exited
+/// with the code if it was waited for. Passed up to `main` to exit with.
+#[derive(Debug)]
+pub struct Relaunched(pub Option<u32>);
+
+impl fmt::Display for Relaunched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "launcher relaunched elevated")
+    }
+}
+
+impl std::error::Error for Relaunched {}
+
+/// Runs the launcher again with the same arguments through the `runas` verb,
+/// so it reloads the config and applies the environment elevated. Returns the
+/// exit code of the elevated launcher if `wait` is set.
+#[cfg(windows)]
+pub fn relaunch_self(wait: bool) -> anyhow::Result<Option<u32>> {
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    let current_dir = std::env::current_dir().context("get current directory")?;
+    let file = HSTRING::from(launcher_path.as_os_str());
+    let parameters = HSTRING::from_wide(&cmdline::join_wide(std::env::args_os().skip(1)));
+    let directory = HSTRING::from(current_dir.as_os_str());
+    let mut info = SHELLEXECUTEINFOW {
+        cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+        fMask: SEE_MASK_NOCLOSEPROCESS,
+        lpVerb: w!("runas"),
+        lpFile: PCWSTR(file.as_ptr()),
+        lpParameters: PCWSTR(parameters.as_ptr()),
+        lpDirectory: PCWSTR(directory.as_ptr()),
+        nShow: SW_SHOWNORMAL.0,
+        ..Default::default()
+    };
+    unsafe {
+        ShellExecuteExW(&mut info).context("relaunch launcher elevated")?;
+        if info.hProcess.is_invalid() {
+            return Ok(None);
+        }
+        let mut exit_code = None;
+        if wait {
+            WaitForSingleObject(info.hProcess, INFINITE);
+            let mut code = 0;
+            GetExitCodeProcess(info.hProcess, &mut code)
+                .context("get exit code of elevated launcher")?;
+            exit_code = Some(code);
+        }
+        let _ = CloseHandle(info.hProcess);
+        Ok(exit_code)
+    }
+}
+
+/// Runs the launcher again with the same arguments through sudo, keeping the
+/// inherited environment where the sudoers policy allows it. sudo is always
+/// waited for, as it may need the terminal to ask for a password.
+#[cfg(not(windows))]
+pub fn relaunch_self(wait: bool) -> anyhow::Result<Option<u32>> {
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    let status = std::process::Command::new("sudo")
+        .arg("--preserve-env")
+        .arg("--")
+        .arg(launcher_path)
+        .args(std::env::args_os().skip(1))
+        .status()
+        .context("relaunch launcher with sudo")?;
+    Ok(wait.then_some(exit_status::code(status) as u32))
+}
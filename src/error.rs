@@ -0,0 +1,155 @@
+use std::fmt;
+#[cfg(windows)]
+use std::io::IsTerminal;
+#[cfg(windows)]
+use windows::core::{w, HSTRING, PCWSTR};
+#[cfg(windows)]
+use windows::Win32::System::Console::{GetConsoleProcessList, GetStdHandle, STD_ERROR_HANDLE};
+#[cfg(windows)]
+use windows::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK};
+
+/// Stable classification of launcher failures for machine-readable output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    ConfigNotFound,
+    ConfigInvalid,
+    CommandNotSpecified,
+    CommandNotFound,
+    SpawnFailed,
+    Timeout,
+    Other,
+}
+
+impl ErrorCode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::ConfigNotFound => "config_not_found",
+            ErrorCode::ConfigInvalid => "config_invalid",
+            ErrorCode::CommandNotSpecified => "command_not_specified",
+            ErrorCode::CommandNotFound => "command_not_found",
+            ErrorCode::SpawnFailed => "spawn_failed",
+            ErrorCode::Timeout => "timeout",
+            ErrorCode::Other => "other",
+        }
+    }
+
+    /// Exit code of the launcher when it fails with this error, kept in the
+    /// 121-127 range so it is unlikely to be confused with the child's own.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCode::ConfigNotFound => 121,
+            ErrorCode::ConfigInvalid => 122,
+            ErrorCode::CommandNotSpecified => 123,
+            ErrorCode::Timeout => 124,
+            ErrorCode::Other => 125,
+            ErrorCode::SpawnFailed => 126,
+            ErrorCode::CommandNotFound => 127,
+        }
+    }
+}
+
+/// Error message tagged with an [`ErrorCode`], used as an anyhow context so the
+/// code can be recovered with `downcast_ref` however deep it sits in the chain.
+#[derive(Debug)]
+pub struct Coded {
+    code: ErrorCode,
+    message: String,
+}
+
+impl Coded {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Coded {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Coded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Coded {}
+
+pub fn code(error: &anyhow::Error) -> ErrorCode {
+    error
+        .downcast_ref::<Coded>()
+        .map_or(ErrorCode::Other, |coded| coded.code)
+}
+
+/// Whether nobody would see what is written to stderr: there is no stderr at
+/// all, or it is a console created just for this process that disappears
+/// with it, as when started from a shortcut.
+#[cfg(windows)]
+fn stderr_unseen() -> bool {
+    unsafe {
+        let handle = GetStdHandle(STD_ERROR_HANDLE).unwrap_or_default();
+        if handle.is_invalid() {
+            return true;
+        }
+        if !std::io::stderr().is_terminal() {
+            return false;
+        }
+        let mut processes = [0u32; 2];
+        GetConsoleProcessList(&mut processes) <= 1
+    }
+}
+
+/// Writes `message` to the Application event log, so failures of a launch
+/// nobody watched can still be looked up.
+#[cfg(windows)]
+fn log_event(message: &str) {
+    unsafe {
+        let Ok(source) = RegisterEventSourceW(None, w!("env-launcher")) else {
+            return;
+        };
+        let message = HSTRING::from(message);
+        let _ = ReportEventW(
+            source,
+            EVENTLOG_ERROR_TYPE,
+            0,
+            0,
+            None,
+            0,
+            Some(&[PCWSTR(message.as_ptr())]),
+            None,
+        );
+        let _ = DeregisterEventSource(source);
+    }
+}
+
+pub fn report(error: &anyhow::Error, json: bool) {
+    if !json {
+        eprintln!("Error: {:?}", error);
+        #[cfg(windows)]
+        if stderr_unseen() {
+            log_event(&format!("{:?}", error));
+            unsafe {
+                MessageBoxW(
+                    None,
+                    &HSTRING::from(format!("{:?}", error)),
+                    &HSTRING::from("env-launcher"),
+                    MB_OK | MB_ICONERROR,
+                );
+            }
+        }
+        return;
+    }
+    let chain: Vec<String> = error.chain().map(ToString::to_string).collect();
+    eprintln!(
+        "{}",
+        serde_json::json!({
+            "error": {
+                "code": code(error).as_str(),
+                "message": error.to_string(),
+                "chain": chain,
+            }
+        })
+    );
+}
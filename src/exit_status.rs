@@ -0,0 +1,85 @@
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+/// Exit code of `status` with nothing lost: the full 32-bit value on Windows,
+/// where crashes end with NTSTATUS codes such as 0xC0000005, and 128 plus the
+/// signal number on Unix for a command ended by a signal, as shells do.
+#[cfg(windows)]
+pub fn code(status: ExitStatus) -> i32 {
+    status.code().unwrap_or(-1)
+}
+
+#[cfg(unix)]
+pub fn code(status: ExitStatus) -> i32 {
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(-1)
+}
+
+/// How the command ended, for messages: `code 3`, `code 0xC0000005` for
+/// NTSTATUS values on Windows or `signal 9` on Unix.
+#[cfg(windows)]
+pub fn describe(status: ExitStatus) -> String {
+    let code = code(status);
+    if code < 0 {
+        format!("code {:#010X}", code as u32)
+    } else {
+        format!("code {}", code)
+    }
+}
+
+#[cfg(unix)]
+pub fn describe(status: ExitStatus) -> String {
+    match status.signal() {
+        Some(signal) => format!("signal {}", signal),
+        None => format!("code {}", code(status)),
+    }
+}
+
+/// Ends the launcher the way the command ended: with its exit code, or on
+/// Unix by the same signal.
+pub fn exit(status: ExitStatus) -> ! {
+    #[cfg(unix)]
+    if let Some(signal) = status.signal() {
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+    std::process::exit(code(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(windows)]
+    use std::os::windows::process::ExitStatusExt;
+
+    #[cfg(windows)]
+    #[test]
+    fn keeps_ntstatus_codes() {
+        let status = ExitStatus::from_raw(0xC0000005);
+        assert_eq!(code(status) as u32, 0xC0000005);
+        assert_eq!(describe(status), "code 0xC0000005");
+        assert_eq!(describe(ExitStatus::from_raw(3)), "code 3");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn maps_signals_above_128() {
+        // Raw wait statuses: the exit code sits in the second byte, a
+        // terminating signal in the low bits.
+        assert_eq!(code(ExitStatus::from_raw(3 << 8)), 3);
+        assert_eq!(
+            code(ExitStatus::from_raw(libc::SIGSEGV)),
+            128 + libc::SIGSEGV
+        );
+        assert_eq!(
+            describe(ExitStatus::from_raw(libc::SIGKILL)),
+            format!("signal {}", libc::SIGKILL)
+        );
+        assert_eq!(describe(ExitStatus::from_raw(1 << 8)), "code 1");
+    }
+}
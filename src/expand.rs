@@ -0,0 +1,109 @@
+use std::ffi::OsString;
+
+/// Expands `${NAME}` and `%NAME%` references in `text` with the values from
+/// `lookup`. References to unknown variables are left as they are; `$$` and
+/// `%%` stand for a literal `$` and `%`.
+pub fn expand(text: &str, lookup: impl Fn(&str) -> Option<OsString>) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(['$', '%']) {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest[1..].starts_with(&rest[..1]) {
+            expanded.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let reference = if let Some(braced) = rest.strip_prefix("${") {
+            braced.find('}').map(|end| (&braced[..end], end + 3))
+        } else if let Some(percent) = rest.strip_prefix('%') {
+            percent
+                .find('%')
+                .filter(|&end| end > 0)
+                .map(|end| (&percent[..end], end + 2))
+        } else {
+            None
+        };
+        match reference.and_then(|(name, len)| Some((lookup(name)?, len))) {
+            Some((value, len)) => {
+                expanded.push_str(&value.to_string_lossy());
+                rest = &rest[len..];
+            }
+            None => {
+                expanded.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Doubles the `$` and `%` in `text`, so that expanding it gives `text` back.
+pub fn escape(text: &str) -> String {
+    text.replace('$', "$$").replace('%', "%%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, expand};
+    use std::ffi::OsString;
+    use std::process::Command;
+
+    fn lookup(name: &str) -> Option<OsString> {
+        match name {
+            "HOME" => Some("/home/me".into()),
+            "EMPTY" => Some("".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn expands_both_forms() {
+        assert_eq!(
+            expand("${HOME}/bin:%HOME%/lib", lookup),
+            "/home/me/bin:/home/me/lib"
+        );
+        assert_eq!(expand("[${EMPTY}]", lookup), "[]");
+    }
+
+    #[test]
+    fn leaves_unknown_and_unfinished_references() {
+        assert_eq!(
+            expand("${MISSING} %MISSING%", lookup),
+            "${MISSING} %MISSING%"
+        );
+        assert_eq!(expand("${HOME", lookup), "${HOME");
+        assert_eq!(expand("100% of $HOME", lookup), "100% of $HOME");
+    }
+
+    #[test]
+    fn unescapes_doubled_markers() {
+        assert_eq!(expand("$${HOME} %%HOME%%", lookup), "${HOME} %HOME%");
+        assert_eq!(expand("cost: 5$$, 10%%", lookup), "cost: 5$, 10%");
+        assert_eq!(expand("$$$${HOME}", lookup), "$${HOME}");
+    }
+
+    #[test]
+    fn escaped_text_expands_to_itself() {
+        for text in ["${HOME}", "%HOME%", "100%", "$$", "a$b%c%"] {
+            assert_eq!(expand(&escape(text), lookup), text);
+        }
+    }
+
+    #[test]
+    fn does_not_expand_values_again() {
+        let lookup = |name: &str| (name == "A").then(|| OsString::from("${A}"));
+        assert_eq!(expand("${A}", lookup), "${A}");
+    }
+
+    #[test]
+    fn looks_names_up_like_the_platform() {
+        let mut command = Command::new("");
+        command.env("Path", "/usr/bin");
+        let lookup = |name: &str| crate::current_var(&command, name, &|_| None);
+        let expected = if cfg!(windows) { "/usr/bin" } else { "${PATH}" };
+        assert_eq!(expand("${PATH}", lookup), expected);
+        assert_eq!(expand("${Path}", lookup), "/usr/bin");
+    }
+}
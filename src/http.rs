@@ -0,0 +1,64 @@
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+use ureq::tls::{TlsConfig, TlsProvider};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct HttpSource {
+    http: String,
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    timeout: Option<Duration>,
+    /// JSON pointer selecting the value from a JSON response.
+    pointer: Option<String>,
+    /// Value used when the request fails.
+    default: Option<String>,
+}
+
+pub fn agent(timeout: Option<Duration>) -> ureq::Agent {
+    ureq::Agent::new_with_config(
+        ureq::Agent::config_builder()
+            .tls_config(
+                TlsConfig::builder()
+                    .provider(TlsProvider::NativeTls)
+                    .build(),
+            )
+            .timeout_global(timeout)
+            .build(),
+    )
+}
+
+impl HttpSource {
+    pub fn fetch(&self) -> anyhow::Result<String> {
+        match self.request() {
+            Ok(value) => Ok(value),
+            Err(error) => match &self.default {
+                Some(default) => {
+                    eprintln!("warning: {:?}", error);
+                    Ok(default.clone())
+                }
+                None => Err(error),
+            },
+        }
+    }
+
+    fn request(&self) -> anyhow::Result<String> {
+        let body = agent(self.timeout)
+            .get(&self.http)
+            .call()
+            .and_then(|mut response| response.body_mut().read_to_string())
+            .with_context(|| format!("fetch {}", self.http))?;
+        let Some(pointer) = &self.pointer else {
+            return Ok(body.trim_end().to_string());
+        };
+        let response: Value = serde_json::from_str(&body)
+            .with_context(|| format!("parse response of {} as json", self.http))?;
+        match response.pointer(pointer) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => anyhow::bail!("response of {} has no {}", self.http, pointer),
+        }
+    }
+}
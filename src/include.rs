@@ -0,0 +1,143 @@
+use crate::profile;
+use toml::Value;
+
+/// Merges a config document over the ones it includes, like a profile, except
+/// that the `append` and `prepend` lists of a variable in both are joined, the
+/// included entries first.
+pub fn merge(base: &mut Value, mut overlay: Value) {
+    let base_env = base.get("env").and_then(Value::as_table);
+    let overlay_env = overlay.get_mut("env").and_then(Value::as_table_mut);
+    if let (Some(base_env), Some(overlay_env)) = (base_env, overlay_env) {
+        for (name, value) in overlay_env.iter_mut() {
+            for key in ["append", "prepend"] {
+                let base_list = base_env
+                    .get(name)
+                    .and_then(|base_value| base_value.get(key))
+                    .and_then(as_list);
+                let Some(base_list) = base_list else {
+                    continue;
+                };
+                if let Some(list) = value.get_mut(key) {
+                    if let Some(overlay_list) = as_list(list) {
+                        *list = Value::Array(base_list.into_iter().chain(overlay_list).collect());
+                    }
+                }
+            }
+        }
+    }
+    profile::merge(base, overlay);
+}
+
+/// An `append` or `prepend` value as a list, also when given as one string.
+fn as_list(value: &Value) -> Option<Vec<Value>> {
+    match value {
+        Value::Array(list) => Some(list.clone()),
+        Value::String(_) => Some(vec![value.clone()]),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge;
+    use std::path::PathBuf;
+    use toml::Value;
+
+    fn document(source: &str) -> Value {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn including_file_takes_precedence() {
+        let mut base = document("command = \"base\"\ncwd = \"dir\"\n[env]\nA = \"1\"\nB = \"1\"\n");
+        merge(
+            &mut base,
+            document("command = \"app\"\n[env]\nB = \"2\"\nC = \"2\"\n"),
+        );
+        assert_eq!(
+            base,
+            document("command = \"app\"\ncwd = \"dir\"\n[env]\nA = \"1\"\nB = \"2\"\nC = \"2\"\n")
+        );
+    }
+
+    #[test]
+    fn joins_append_and_prepend_lists() {
+        let mut base = document(
+            "[env]\nPATH = { prepend = [\"/base\"], append = \"/base-tail\" }\nOTHER = { append = [\"x\"] }\n",
+        );
+        merge(
+            &mut base,
+            document("[env]\nPATH = { prepend = \"/app\", append = [\"/app-tail\"] }\nOTHER = \"plain\"\n"),
+        );
+        assert_eq!(
+            base["env"]["PATH"],
+            document("prepend = [\"/base\", \"/app\"]\nappend = [\"/base-tail\", \"/app-tail\"]\n")
+        );
+        assert_eq!(base["env"]["OTHER"].as_str(), Some("plain"));
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "env-launcher-include-{}-{}",
+                name,
+                std::process::id()
+            ));
+            std::fs::create_dir_all(dir.join("shared")).unwrap();
+            TempDir(dir)
+        }
+
+        fn write(&self, name: &str, content: &str) -> PathBuf {
+            let path = self.0.join(name);
+            std::fs::write(&path, content).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn reads_nested_includes_relative_to_each_file() {
+        let dir = TempDir::new("nested");
+        dir.write(
+            "shared/base.toml",
+            "command = \"base\"\nargs = [\"--base\"]\n[env]\nLEVEL = \"base\"\nPATH = { append = [\"/base\"] }\n",
+        );
+        dir.write(
+            "shared/team.toml",
+            "include = [\"base.toml\"]\nargs = [\"--team\"]\n[env]\nLEVEL = \"team\"\nPATH = { append = [\"/team\"] }\n",
+        );
+        let app = dir.write(
+            "app.toml",
+            "include = [\"shared/team.toml\"]\n[env]\nPATH = { append = [\"/app\"] }\n",
+        );
+        let merged = crate::read_document(&app, None, &mut Vec::new()).unwrap();
+        assert_eq!(merged["command"].as_str(), Some("base"));
+        assert_eq!(merged["args"], document("a = [\"--team\"]")["a"]);
+        assert_eq!(merged["env"]["LEVEL"].as_str(), Some("team"));
+        assert_eq!(
+            merged["env"]["PATH"]["append"],
+            document("a = [\"/base\", \"/team\", \"/app\"]")["a"]
+        );
+        assert!(merged.get("include").is_none());
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let dir = TempDir::new("cycle");
+        dir.write("a.toml", "include = [\"b.toml\"]\n");
+        let b = dir.write("b.toml", "include = [\"a.toml\"]\n");
+        let error = crate::read_document(&b, None, &mut Vec::new()).unwrap_err();
+        assert!(
+            error.to_string().ends_with("b.toml includes itself"),
+            "{}",
+            error
+        );
+    }
+}
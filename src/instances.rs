@@ -0,0 +1,163 @@
+use anyhow::Context;
+#[cfg(unix)]
+use std::fs::File;
+#[cfg(unix)]
+use std::os::fd::AsRawFd;
+use std::path::Path;
+#[cfg(windows)]
+use windows::core::HSTRING;
+#[cfg(windows)]
+use windows::Win32::Foundation::{
+    CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE, WAIT_ABANDONED, WAIT_OBJECT_0,
+    WAIT_TIMEOUT,
+};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    CreateMutexW, CreateSemaphoreW, ReleaseMutex, ReleaseSemaphore, WaitForSingleObject, INFINITE,
+};
+
+/// A slot of the named semaphore that bounds how many children launched from
+/// the same config run at once. The slot is given back when dropped.
+#[cfg(windows)]
+pub struct InstanceSlot(HANDLE);
+
+#[cfg(not(windows))]
+pub struct InstanceSlot;
+
+impl InstanceSlot {
+    #[cfg(windows)]
+    pub fn acquire(key: &Path, max_instances: u32, wait: bool) -> anyhow::Result<Self> {
+        let name = HSTRING::from(format!("Local\\env-launcher-{:016x}", fnv1a(key)));
+        let max_instances = i32::try_from(max_instances).unwrap_or(i32::MAX);
+        unsafe {
+            let semaphore = CreateSemaphoreW(None, max_instances, max_instances, &name)
+                .context("create instance semaphore")?;
+            let mut result = WaitForSingleObject(semaphore, 0);
+            if result == WAIT_TIMEOUT && wait {
+                eprintln!(
+                    "{} instances already running, waiting for one to exit",
+                    max_instances
+                );
+                result = WaitForSingleObject(semaphore, INFINITE);
+            }
+            if result == WAIT_OBJECT_0 {
+                return Ok(InstanceSlot(semaphore));
+            }
+            let error = windows::core::Error::from_thread();
+            let _ = CloseHandle(semaphore);
+            if result == WAIT_TIMEOUT {
+                anyhow::bail!("{} instances are already running", max_instances);
+            }
+            Err(error).context("wait for instance semaphore")
+        }
+    }
+
+    #[cfg(not(windows))]
+    pub fn acquire(_key: &Path, _max_instances: u32, _wait: bool) -> anyhow::Result<Self> {
+        anyhow::bail!("max_instances is only supported on Windows")
+    }
+}
+
+#[cfg(windows)]
+impl Drop for InstanceSlot {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ReleaseSemaphore(self.0, 1, None);
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// Lock held by the one launch of a config that may run at a time, released
+/// when dropped. A named mutex on Windows, a lock file in the temporary
+/// directory elsewhere.
+#[cfg(windows)]
+pub struct InstanceLock(HANDLE);
+
+#[cfg(unix)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Takes the lock `name`, or the one of the config or command at `key`.
+    /// Returns `None` if another launcher holds it, unless `wait` is set to
+    /// wait for it to be released.
+    #[cfg(windows)]
+    pub fn acquire(name: Option<&str>, key: &Path, wait: bool) -> anyhow::Result<Option<Self>> {
+        let name = HSTRING::from(format!("Local\\env-launcher-{}", lock_name(name, key)));
+        unsafe {
+            let mutex = CreateMutexW(None, true, &name).context("create instance mutex")?;
+            if GetLastError() != ERROR_ALREADY_EXISTS {
+                return Ok(Some(InstanceLock(mutex)));
+            }
+            if wait {
+                eprintln!("already running, waiting for it to exit");
+                let result = WaitForSingleObject(mutex, INFINITE);
+                if result == WAIT_OBJECT_0 || result == WAIT_ABANDONED {
+                    return Ok(Some(InstanceLock(mutex)));
+                }
+            }
+            let _ = CloseHandle(mutex);
+            Ok(None)
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn acquire(name: Option<&str>, key: &Path, wait: bool) -> anyhow::Result<Option<Self>> {
+        let path = std::env::temp_dir().join(format!("env-launcher-{}.lock", lock_name(name, key)));
+        let file =
+            File::create(&path).with_context(|| format!("create lock file {}", path.display()))?;
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+            return Ok(Some(InstanceLock { _file: file }));
+        }
+        if !wait {
+            return Ok(None);
+        }
+        eprintln!("already running, waiting for it to exit");
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("lock {}", path.display()));
+        }
+        Ok(Some(InstanceLock { _file: file }))
+    }
+}
+
+#[cfg(windows)]
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ReleaseMutex(self.0);
+            let _ = CloseHandle(self.0);
+        }
+    }
+}
+
+/// `name` with only the characters safe in mutex and file names, or a hash of
+/// `key`.
+fn lock_name(name: Option<&str>, key: &Path) -> String {
+    match name {
+        Some(name) => name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect(),
+        None => format!("single-{:016x}", fnv1a(key)),
+    }
+}
+
+/// Stable hash of the path, so every launcher build agrees on the name.
+fn fnv1a(path: &Path) -> u64 {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    path.to_string_lossy()
+        .to_lowercase()
+        .bytes()
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+        })
+}
@@ -0,0 +1,186 @@
+#[cfg(windows)]
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+#[cfg(unix)]
+use std::cell::Cell;
+#[cfg(windows)]
+use std::os::windows::io::AsRawHandle;
+#[cfg(windows)]
+use std::process::Child;
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+#[cfg(windows)]
+use windows::Win32::System::JobObjects::{
+    AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicUIRestrictions,
+    JobObjectExtendedLimitInformation, SetInformationJobObject, JOBOBJECT_BASIC_UI_RESTRICTIONS,
+    JOBOBJECT_EXTENDED_LIMIT_INFORMATION, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE, JOB_OBJECT_UILIMIT,
+    JOB_OBJECT_UILIMIT_DESKTOP, JOB_OBJECT_UILIMIT_DISPLAYSETTINGS, JOB_OBJECT_UILIMIT_EXITWINDOWS,
+    JOB_OBJECT_UILIMIT_GLOBALATOMS, JOB_OBJECT_UILIMIT_HANDLES, JOB_OBJECT_UILIMIT_READCLIPBOARD,
+    JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS, JOB_OBJECT_UILIMIT_WRITECLIPBOARD,
+};
+
+/// User interface actions denied to the child; each `true` entry is a
+/// JOB_OBJECT_UILIMIT flag.
+#[derive(Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct UiLimits {
+    read_clipboard: bool,
+    write_clipboard: bool,
+    display_settings: bool,
+    exit_windows: bool,
+    desktop: bool,
+    global_atoms: bool,
+    handles: bool,
+    system_parameters: bool,
+}
+
+impl UiLimits {
+    #[cfg(windows)]
+    fn flags(&self) -> JOB_OBJECT_UILIMIT {
+        let flags = [
+            (self.read_clipboard, JOB_OBJECT_UILIMIT_READCLIPBOARD),
+            (self.write_clipboard, JOB_OBJECT_UILIMIT_WRITECLIPBOARD),
+            (self.display_settings, JOB_OBJECT_UILIMIT_DISPLAYSETTINGS),
+            (self.exit_windows, JOB_OBJECT_UILIMIT_EXITWINDOWS),
+            (self.desktop, JOB_OBJECT_UILIMIT_DESKTOP),
+            (self.global_atoms, JOB_OBJECT_UILIMIT_GLOBALATOMS),
+            (self.handles, JOB_OBJECT_UILIMIT_HANDLES),
+            (self.system_parameters, JOB_OBJECT_UILIMIT_SYSTEMPARAMETERS),
+        ];
+        JOB_OBJECT_UILIMIT(
+            flags
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .fold(0, |flags, (_, flag)| flags | flag.0),
+        )
+    }
+}
+
+/// A job object the child is placed in; the handle is closed on drop.
+#[cfg(windows)]
+pub struct Job(HANDLE);
+
+#[cfg(windows)]
+impl Job {
+    pub fn create() -> anyhow::Result<Self> {
+        let handle = unsafe { CreateJobObjectW(None, None) }.context("create job object")?;
+        Ok(Job(handle))
+    }
+
+    pub fn set_ui_limits(&self, limits: &UiLimits) -> anyhow::Result<()> {
+        let info = JOBOBJECT_BASIC_UI_RESTRICTIONS {
+            UIRestrictionsClass: limits.flags(),
+        };
+        unsafe {
+            SetInformationJobObject(
+                self.0,
+                JobObjectBasicUIRestrictions,
+                &info as *const _ as *const _,
+                size_of::<JOBOBJECT_BASIC_UI_RESTRICTIONS>() as u32,
+            )
+        }
+        .context("set job ui limits")
+    }
+
+    /// Makes closing the job, which happens at the latest when the launcher
+    /// exits, terminate every process in it.
+    pub fn set_kill_on_close(&self) -> anyhow::Result<()> {
+        let mut info = JOBOBJECT_EXTENDED_LIMIT_INFORMATION::default();
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        unsafe {
+            SetInformationJobObject(
+                self.0,
+                JobObjectExtendedLimitInformation,
+                &info as *const _ as *const _,
+                size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        }
+        .context("set job kill on close")
+    }
+
+    pub fn assign(&self, child: &Child) -> anyhow::Result<()> {
+        unsafe { AssignProcessToJobObject(self.0, HANDLE(child.as_raw_handle())) }
+            .context("assign child to job object")
+    }
+}
+
+#[cfg(windows)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.0) };
+    }
+}
+
+/// The process group the child leads, if it was started in one of its own,
+/// killed on drop with `set_kill_on_close`. A child in the launcher's group
+/// is only ended by [`kill_with_launcher`].
+#[cfg(unix)]
+pub struct Job {
+    group: Cell<Option<libc::pid_t>>,
+    kill_on_close: Cell<bool>,
+}
+
+#[cfg(unix)]
+impl Job {
+    pub fn create() -> anyhow::Result<Self> {
+        Ok(Job {
+            group: Cell::new(None),
+            kill_on_close: Cell::new(false),
+        })
+    }
+
+    pub fn set_ui_limits(&self, _limits: &UiLimits) -> anyhow::Result<()> {
+        anyhow::bail!("ui_limits are only supported on Windows")
+    }
+
+    /// Makes dropping the job, when the launcher is done with the child,
+    /// kill every process left in its group.
+    pub fn set_kill_on_close(&self) -> anyhow::Result<()> {
+        self.kill_on_close.set(true);
+        Ok(())
+    }
+
+    pub fn assign(&self, child: &std::process::Child) -> anyhow::Result<()> {
+        let pid = child.id() as libc::pid_t;
+        if unsafe { libc::getpgid(pid) } == pid {
+            self.group.set(Some(pid));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Job {
+    fn drop(&mut self) {
+        if let (true, Some(group)) = (self.kill_on_close.get(), self.group.get()) {
+            unsafe { libc::killpg(group, libc::SIGKILL) };
+        }
+    }
+}
+
+/// Has the kernel kill the command once the launcher is gone, on Linux. The
+/// command stays in the launcher's process group, and so in the foreground of
+/// its terminal.
+#[cfg(unix)]
+pub fn kill_with_launcher(command: &mut std::process::Command) {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::process::CommandExt;
+        let launcher = std::process::id() as libc::pid_t;
+        unsafe {
+            command.pre_exec(move || {
+                if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                // The launcher may have exited before the signal was set up.
+                if libc::getppid() != launcher {
+                    return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = command;
+}
@@ -0,0 +1,61 @@
+use crate::{build_command, launch, redirect_streams, resolved_environment, spawn_error, Config};
+use std::ffi::OsString;
+use std::process::{Child, Command, ExitStatus};
+
+/// Launches the command a [`Config`] describes, the way the launcher binary
+/// does.
+///
+/// ```no_run
+/// let config = env_launcher::Config::load("app.toml", None)?;
+/// let status = env_launcher::Launcher::new(config).run()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct Launcher {
+    config: Config,
+}
+
+impl Launcher {
+    pub fn new(config: Config) -> Self {
+        Launcher { config }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.config
+    }
+
+    /// The command with its arguments, working directory and environment
+    /// applied, ready to be started.
+    pub fn command(&self) -> anyhow::Result<Command> {
+        build_command(&self.config)
+    }
+
+    /// The full environment the command starts with, sorted by name.
+    pub fn environment(&self) -> anyhow::Result<Vec<(OsString, OsString)>> {
+        let command = self.command()?;
+        Ok(resolved_environment(&self.config, &command))
+    }
+
+    /// Starts the command with its output redirected as configured, leaving
+    /// waiting for it to the caller. Hooks, restarts and the settings applied
+    /// to a running child, such as `priority` or `timeout`, are not used.
+    pub fn spawn(&self) -> anyhow::Result<Child> {
+        let mut command = self.command()?;
+        redirect_streams(&self.config, &mut command, false)?;
+        command
+            .spawn()
+            .map_err(|error| spawn_error(error, self.config.command.as_path()))
+    }
+
+    /// Runs the command with every configured option, from `wait_for` and
+    /// the hooks to restarts, and returns its exit status unless it was
+    /// detached. `elevate = true` and `exit_code_file` on a detached command
+    /// start the current executable again, so they need it to be the
+    /// launcher.
+    pub fn run(&self) -> anyhow::Result<Option<ExitStatus>> {
+        launch(&self.config, false)
+    }
+}
@@ -0,0 +1,2327 @@
+//! Starts a command with an environment described by a TOML config file.
+//!
+//! [`Config::load`] reads a config and [`Launcher`] starts the command it
+//! describes, the same way the `env-launcher` binary does.
+
+use anyhow::Context;
+use error::{Coded, ErrorCode};
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+#[cfg(unix)]
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+#[cfg(windows)]
+use std::os::windows::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime};
+
+pub use http::HttpSource;
+pub use launcher::Launcher;
+pub use transform::Transform;
+pub use vault::VaultSecret;
+
+#[doc(hidden)]
+pub mod cli;
+mod cmdline;
+mod console;
+mod credential;
+mod dotenv;
+mod edit;
+mod elevate;
+mod error;
+mod exit_status;
+mod expand;
+mod http;
+mod include;
+mod instances;
+mod job;
+mod launcher;
+mod logfile;
+mod output;
+mod package;
+mod pidfile;
+mod power;
+mod priority;
+#[cfg_attr(windows, path = "processes/windows.rs")]
+#[cfg_attr(unix, path = "processes/unix.rs")]
+mod processes;
+mod profile;
+mod report;
+mod run_as;
+mod script;
+mod shortcut;
+mod signals;
+mod snapshot;
+mod startup;
+mod system_env;
+mod terminal;
+mod trace;
+mod transform;
+mod validate;
+mod vault;
+mod verbose;
+mod wait_for;
+mod watch;
+mod when;
+mod which;
+mod window;
+
+trait ToOsString {
+    fn to_os_string(&self) -> OsString;
+}
+
+impl<T: AsRef<OsStr>> ToOsString for T {
+    #[inline]
+    fn to_os_string(&self) -> OsString {
+        self.as_ref().to_os_string()
+    }
+}
+
+/// Value of a variable in `[env]`.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum EnvConfig {
+    Simple(String),
+    Checked {
+        value: String,
+        must_match: Option<String>,
+        #[serde(default)]
+        must_be_dir: bool,
+        /// Only print a warning instead of aborting the launch on a violation.
+        #[serde(default)]
+        warn_only: bool,
+    },
+    From {
+        from: String,
+        #[serde(default)]
+        transform: Vec<transform::Transform>,
+    },
+    Vault {
+        vault: vault::VaultSecret,
+    },
+    Http(http::HttpSource),
+    /// Content of a file, relative to the config file, without the trailing
+    /// newline.
+    File {
+        file: PathBuf,
+    },
+    /// Password from the system's credential store.
+    Credential {
+        credential: String,
+    },
+    /// Trimmed stdout of a helper command, run in the environment set so far.
+    FromCommand {
+        from_command: Vec<String>,
+    },
+    /// Only set when the variable isn't set yet.
+    IfUnset {
+        if_unset: String,
+    },
+    /// Removes the variable with `unset = true`.
+    Unset {
+        unset: bool,
+    },
+    // Every field is optional, so this must come last to not shadow the
+    // variants above.
+    Detailed {
+        #[serde(default, deserialize_with = "string_or_list")]
+        #[schemars(with = "StringOrList")]
+        append: Vec<String>,
+        #[serde(default, deserialize_with = "string_or_list")]
+        #[schemars(with = "StringOrList")]
+        prepend: Vec<String>,
+        /// Defaults to the platform's PATH separator, which `"path"` also
+        /// stands for.
+        sep: Option<String>,
+        /// Drop empty and repeated entries, keeping the first occurrence.
+        #[serde(default)]
+        dedup: bool,
+        /// Make entries absolute paths without trailing separators.
+        #[serde(default)]
+        normalize: bool,
+    },
+}
+
+impl EnvConfig {
+    /// Whether the value is fetched from elsewhere or computed by a command.
+    fn is_dynamic(&self) -> bool {
+        matches!(
+            self,
+            EnvConfig::Vault { .. }
+                | EnvConfig::Http(_)
+                | EnvConfig::Credential { .. }
+                | EnvConfig::FromCommand { .. }
+        )
+    }
+}
+
+/// One string or a list of them.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum StringOrList {
+    String(String),
+    List(Vec<String>),
+}
+
+fn string_or_list<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(value) => vec![value],
+        StringOrList::List(values) => values,
+    })
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LoopConfig {
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    every: Duration,
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "String")]
+    jitter: Duration,
+    #[serde(default)]
+    max_runs: u32,
+}
+
+/// Restarts of the command after it exits, waiting twice as long before each
+/// further restart.
+#[derive(Deserialize, JsonSchema)]
+struct RestartConfig {
+    /// Unlimited if not set.
+    max_retries: Option<u32>,
+    #[serde(default = "default_restart_delay", with = "humantime_serde")]
+    #[schemars(with = "String")]
+    delay: Duration,
+    /// Upper bound of the growing delay.
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    max_delay: Option<Duration>,
+    #[serde(default)]
+    on: RestartOn,
+}
+
+fn default_restart_delay() -> Duration {
+    Duration::from_secs(1)
+}
+
+#[derive(Deserialize, JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RestartOn {
+    /// Only when the exit code doesn't count as success.
+    #[default]
+    Failure,
+    Always,
+}
+
+/// Casing applied to the names of the variables passed to the command.
+#[derive(Deserialize, JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum NameCase {
+    Upper,
+    #[default]
+    Preserve,
+}
+
+/// Process whose exit also ends the command.
+#[derive(Deserialize, JsonSchema)]
+struct LifetimeConfig {
+    /// Variable holding the process id to follow.
+    follow_pid_env: Option<String>,
+    /// Follow the process that started the launcher.
+    #[serde(default)]
+    follow_parent: bool,
+}
+
+/// Program whose running instance makes the launcher exit without starting
+/// the command.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum SkipIfRunningConfig {
+    Process(String),
+    Detailed {
+        process: Option<String>,
+        window_class: Option<String>,
+        /// Bring the running instance's window to the foreground.
+        #[serde(default)]
+        focus: bool,
+    },
+}
+
+/// Keeps a second launch of the same config from starting the command again.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum SingleInstanceConfig {
+    Enabled(bool),
+    Detailed {
+        /// Shared by the configs that must not run together; derived from the
+        /// config file path if not set.
+        name: Option<String>,
+        #[serde(default)]
+        on_running: OnRunning,
+    },
+}
+
+/// What a launch does when another launch holds the single-instance lock.
+#[derive(Deserialize, JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum OnRunning {
+    #[default]
+    Exit,
+    /// Bring the running command's window to the foreground and exit.
+    Focus,
+    /// Start the command once the running launch has ended.
+    Wait,
+}
+
+/// A running process, by id or by executable name.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum ProcessRef {
+    Pid(u32),
+    Name(String),
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct WatchdogConfig {
+    file: PathBuf,
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "String")]
+    stale_after: Duration,
+}
+
+/// Directory relative paths in the config are resolved against.
+#[derive(Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum RelativeTo {
+    /// The config file's directory, also for `command` and env values.
+    Config,
+    /// The directory the launcher is run from, also for `cwd` and files.
+    Cwd,
+}
+
+/// How a child that runs past `timeout` is ended.
+#[derive(Deserialize, JsonSchema, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TimeoutAction {
+    #[default]
+    Kill,
+    /// Ask the child to exit first, killing it if it is still running after
+    /// a grace period.
+    Terminate,
+}
+
+/// Ends of the pipes between `[[processes]]` entries a command is connected
+/// to, in place of its own stdin and stdout.
+#[derive(Default)]
+struct PipeEnds {
+    stdin: Option<io::PipeReader>,
+    stdout: Option<io::PipeWriter>,
+}
+
+/// How waiting for the child ended.
+enum WaitEnd {
+    Exited(ExitStatus),
+    /// Killed by the watchdog, to be restarted.
+    StaleHeartbeat,
+    /// Stopped because the watched file changed.
+    Changed(PathBuf),
+    TimedOut(ExitStatus),
+}
+
+/// The program to launch, an executable or script path or a packaged app.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum CommandConfig {
+    Path(PathBuf),
+    App { aumid: String },
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        CommandConfig::Path(PathBuf::new())
+    }
+}
+
+impl CommandConfig {
+    /// The path of the command, or the AppUserModelID of a packaged app, for
+    /// messages and as a key.
+    fn as_path(&self) -> &Path {
+        match self {
+            CommandConfig::Path(path) => path,
+            CommandConfig::App { aumid } => Path::new(aumid),
+        }
+    }
+}
+
+/// Smoke check run by `launcher test` in place of the main command.
+#[derive(Deserialize, JsonSchema)]
+struct CheckConfig {
+    command: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    exit_code: i32,
+    /// Regular expression the combined stdout and stderr must match.
+    output: Option<String>,
+}
+
+/// Setup or teardown command run in the environment of the main command.
+#[derive(Deserialize, JsonSchema)]
+struct HookConfig {
+    command: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    /// Variables set for this command only.
+    #[serde(default)]
+    env: IndexMap<String, String>,
+    cwd: Option<PathBuf>,
+}
+
+/// A launcher config file.
+#[derive(Deserialize, JsonSchema, Default)]
+#[serde(default)]
+pub struct Config {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    command: CommandConfig,
+    args: Vec<String>,
+    args_string: Option<String>,
+    /// Program name the command sees as its first argument, on Unix.
+    arg0: Option<String>,
+    cwd: Option<PathBuf>,
+    /// `cwd`, log, dotenv, report, exit code and heartbeat files are relative
+    /// to the config file unless this is `"cwd"`.
+    relative_to: Option<RelativeTo>,
+    /// Applied in file order, so values can refer to variables set above them.
+    env: IndexMap<String, EnvConfig>,
+    detach: bool,
+    /// Time a detached command has to keep running, or to exit successfully,
+    /// for the launch to succeed.
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    startup_grace: Option<Duration>,
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    delay: Option<Duration>,
+    delay_message: Option<String>,
+    delay_countdown: bool,
+    #[serde(rename = "loop")]
+    loop_mode: Option<LoopConfig>,
+    watchdog: Option<WatchdogConfig>,
+    /// Restart the command when one of these files changes.
+    watch: Option<watch::WatchConfig>,
+    /// Also stop the command when the config file changes, set by `--watch`.
+    #[serde(skip)]
+    #[schemars(skip)]
+    watch_config: bool,
+    restart: Option<RestartConfig>,
+    /// Time after which the command is ended and the launcher fails.
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    timeout: Option<Duration>,
+    on_timeout: TimeoutAction,
+    exit_code_file: Option<PathBuf>,
+    debug_output: bool,
+    output_encoding: Option<output::Encoding>,
+    new_process_group: bool,
+    max_instances: Option<u32>,
+    instance_wait: bool,
+    utf8_console: bool,
+    /// Path of a JSON report written for every run; `{timestamp}` is replaced
+    /// with the start time.
+    report: Option<String>,
+    check: Option<CheckConfig>,
+    elevate: Option<elevate::Elevate>,
+    /// Account the command is started under instead of the launcher's.
+    run_as: Option<run_as::RunAs>,
+    normalize_names: NameCase,
+    /// Command run after the child exits abnormally.
+    on_crash: Vec<String>,
+    lifetime: Option<LifetimeConfig>,
+    skip_if_running: Option<SkipIfRunningConfig>,
+    single_instance: Option<SingleInstanceConfig>,
+    /// Conditions waited for before the command is started.
+    wait_for: Option<wait_for::WaitFor>,
+    /// Values for `{var:NAME}` placeholders, not passed to the command.
+    vars: BTreeMap<String, String>,
+    /// Exit codes of the command that count as success, `[0]` if empty.
+    success_codes: Vec<i32>,
+    ui_limits: Option<job::UiLimits>,
+    /// Process whose environment replaces the launcher's own as the base.
+    inherit_from_pid: Option<ProcessRef>,
+    /// Start from the machine and user environment stored in the registry
+    /// instead of the launcher's possibly outdated one.
+    reload_system_env: bool,
+    efficiency_mode: bool,
+    keep_awake: Option<power::KeepAwake>,
+    /// Start from an empty environment instead of the inherited one.
+    env_clear: bool,
+    /// Inherited variables still passed on with `env_clear`.
+    env_keep: Vec<String>,
+    /// Inherited variables never passed on.
+    env_remove: Vec<String>,
+    /// Dotenv files loaded before `[env]`, relative to the config file.
+    env_files: Vec<PathBuf>,
+    /// Kill the command instead of passing on a request to terminate the
+    /// launcher.
+    kill_on_terminate: bool,
+    /// Commands run in order before the command is started; a failing one
+    /// aborts the launch.
+    pre: Vec<HookConfig>,
+    /// Commands run in order after the command exited, with its exit code in
+    /// LAUNCHER_EXIT_CODE.
+    post: Vec<HookConfig>,
+    priority: Option<priority::Priority>,
+    /// Numbers of the CPUs the command may run on.
+    affinity: Vec<u32>,
+    /// Start a console command without a console window, still waiting for
+    /// it unlike `detach`.
+    hide_console: bool,
+    /// Run `command` as a snippet of shell code.
+    shell: Option<script::ShellConfig>,
+    /// Written once the command started and removed after it exited, unless
+    /// the launcher doesn't wait for it.
+    pid_file: Option<pidfile::PidFile>,
+    /// File the command reads its input from, or `"null"` for none.
+    stdin: Option<PathBuf>,
+    /// Files the command's output is written to, also when detached, or
+    /// `"null"` to discard it. Both streams may share one file, and
+    /// `stderr = "stdout"` merges them wherever stdout goes.
+    stdout: Option<logfile::LogFile>,
+    stderr: Option<logfile::LogFile>,
+    /// On Unix, replace the launcher with the command instead of waiting for
+    /// it; nothing configured to happen after the start applies.
+    exec: bool,
+    /// Kill the command and every process it started once the launcher exits.
+    /// Has no effect when the launcher doesn't wait for the command.
+    kill_on_exit: bool,
+    /// Config files the rest of this one is merged over, relative to it.
+    include: Vec<PathBuf>,
+    /// Named variants selected with `--profile`, merged over the other fields.
+    #[schemars(with = "BTreeMap<String, serde_json::Map<String, serde_json::Value>>")]
+    profiles: BTreeMap<String, toml::Value>,
+    /// Fields merged over the others when the condition in the key holds.
+    #[schemars(with = "IndexMap<String, serde_json::Map<String, serde_json::Value>>")]
+    when: IndexMap<String, toml::Value>,
+    /// Commands started side by side, each merged over the other fields.
+    #[schemars(with = "Vec<serde_json::Map<String, serde_json::Value>>")]
+    processes: Vec<toml::Value>,
+    /// Time between starting one of `[[processes]]` and the next.
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    stagger: Option<Duration>,
+    /// Time a `[[processes]]` entry waits after the one before it was
+    /// started, instead of `stagger`.
+    #[serde(with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    start_delay: Option<Duration>,
+    /// Name of a `[[processes]]` entry, for `pipe_to` to refer to.
+    name: Option<String>,
+    /// The `[[processes]]` entry this one's stdout is fed into as stdin.
+    pipe_to: Option<String>,
+    #[serde(skip)]
+    #[schemars(skip)]
+    process_configs: Vec<Config>,
+}
+
+impl Config {
+    /// Reads the config file at `path`, applying the profile `profile` if
+    /// given.
+    pub fn load<P: AsRef<Path>>(path: P, profile: Option<&str>) -> anyhow::Result<Config> {
+        load_config(path, profile, None)
+    }
+
+    /// Variables set for the command, in the order they are applied.
+    pub fn env(&self) -> &IndexMap<String, EnvConfig> {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut IndexMap<String, EnvConfig> {
+        &mut self.env
+    }
+}
+
+/// Marks where CLI arguments are inserted into the configured `args`.
+const ARGS_PLACEHOLDER: &str = "{args}";
+const LAUNCH_ID_PLACEHOLDER: &str = "{launch_id}";
+/// Variable the command gets the launch id in. It changes on every launch, so
+/// it is left out wherever the resolved environment is stored or compared.
+const LAUNCH_ID_VAR: &str = "LAUNCH_ID";
+/// Starts a `{env:NAME}` placeholder for a variable of the launcher's own
+/// environment.
+const ENV_PLACEHOLDER: &str = "{env:";
+
+/// Time a child asked to exit on timeout gets before it is killed.
+const TERMINATE_GRACE: Duration = Duration::from_secs(5);
+
+#[cfg(windows)]
+const PATH_SEP: &str = ";";
+#[cfg(unix)]
+const PATH_SEP: &str = ":";
+
+// https://learn.microsoft.com/en-us/windows/win32/procthread/process-creation-flags
+#[cfg(windows)]
+const DETACHED_PROCESS: u32 = 0x00000008;
+#[cfg(windows)]
+const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+#[cfg(windows)]
+const IDLE_PRIORITY_CLASS: u32 = 0x00000040;
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+fn load_config<P: AsRef<Path>>(
+    config_path: P,
+    profile: Option<&str>,
+    format: Option<&str>,
+) -> anyhow::Result<Config> {
+    let config_path = config_path.as_ref();
+    let mut document = load_document(config_path, profile, format)?;
+    let mut config = config_from_document(document.clone(), config_path)?;
+    if !config.processes.is_empty() {
+        // Every process is the rest of the file with its own entry merged
+        // over it.
+        if let Some(table) = document.as_table_mut() {
+            table.remove("processes");
+        }
+        config.process_configs = config
+            .processes
+            .iter()
+            .map(|process| {
+                let mut process_document = document.clone();
+                profile::merge(&mut process_document, process.clone());
+                config_from_document(process_document, config_path)
+            })
+            .collect::<anyhow::Result<_>>()?;
+    }
+    Ok(config)
+}
+
+/// Reads a config file with its includes, profile and `when` blocks applied,
+/// before it is turned into a [`Config`].
+fn load_document(
+    config_path: &Path,
+    profile: Option<&str>,
+    format: Option<&str>,
+) -> anyhow::Result<toml::Value> {
+    let mut document = read_document(config_path, format, &mut Vec::new())?;
+    if let Some(profile) = profile {
+        verbose::log(format_args!("applying profile {}", profile));
+        profile::apply(&mut document, profile)
+            .with_context(|| format!("select profile of {}", config_path.display()))?;
+    }
+    when::apply(&mut document)
+        .with_context(|| format!("apply when blocks of {}", config_path.display()))?;
+    Ok(document)
+}
+
+/// Reads a config file merged over the files it includes. `including` holds
+/// the files whose includes are being read, to catch include cycles.
+fn read_document(
+    config_path: &Path,
+    format: Option<&str>,
+    including: &mut Vec<PathBuf>,
+) -> anyhow::Result<toml::Value> {
+    verbose::log(format_args!(
+        "reading config file {}",
+        config_path.display()
+    ));
+    let config_content = std::fs::read(config_path).map_err(|error| {
+        let code = match error.kind() {
+            io::ErrorKind::NotFound => ErrorCode::ConfigNotFound,
+            _ => ErrorCode::ConfigInvalid,
+        };
+        anyhow::Error::new(error).context(Coded::new(
+            code,
+            format!("read config file {}", config_path.display()),
+        ))
+    })?;
+    let parse_error = || {
+        Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!("parse config file {}", config_path.display()),
+        )
+    };
+    let mut document =
+        parse_document(&config_content, config_path, format).with_context(parse_error)?;
+    let Some(include) = document
+        .as_table_mut()
+        .and_then(|table| table.remove("include"))
+    else {
+        return Ok(document);
+    };
+    let absolute_path = std::path::absolute(config_path).context("get config path")?;
+    if including.contains(&absolute_path) {
+        return Err(Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!("config file {} includes itself", config_path.display()),
+        )
+        .into());
+    }
+    including.push(absolute_path);
+    let dir = config_path.parent().unwrap_or(Path::new(""));
+    let mut merged = toml::Value::Table(Default::default());
+    for path in Vec::<PathBuf>::deserialize(include).with_context(parse_error)? {
+        include::merge(
+            &mut merged,
+            read_document(&dir.join(path), None, including)?,
+        );
+    }
+    including.pop();
+    include::merge(&mut merged, document);
+    Ok(merged)
+}
+
+/// Parses a config file as `format`, or by its extension if not given: JSON
+/// for `.json`, YAML for `.yaml` and `.yml`, TOML otherwise.
+fn parse_document(
+    content: &[u8],
+    config_path: &Path,
+    format: Option<&str>,
+) -> anyhow::Result<toml::Value> {
+    let extension = config_path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    Ok(match format.or(extension.as_deref()) {
+        Some("json") => serde_json::from_slice(content)?,
+        Some("yaml" | "yml") => serde_yaml::from_slice(content)?,
+        _ => toml::from_slice(content)?,
+    })
+}
+
+fn config_from_document(document: toml::Value, config_path: &Path) -> anyhow::Result<Config> {
+    let mut config: Config = document.try_into().with_context(|| {
+        Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!("parse config file {}", config_path.display()),
+        )
+    })?;
+    if let Some(args_string) = config.args_string.take() {
+        if !config.args.is_empty() {
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!(
+                    "config file {} sets both args and args_string",
+                    config_path.display()
+                ),
+            )
+            .into());
+        }
+        config.args = cmdline::split(&args_string);
+    }
+    // `CreateProcessW` would take another first token, but `std::process`
+    // always starts the command line with the program.
+    if cfg!(windows) && config.arg0.is_some() {
+        return Err(Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!(
+                "config file {} sets arg0, which is only supported on Unix",
+                config_path.display()
+            ),
+        )
+        .into());
+    }
+    expand_vars(&mut config);
+    config.path = Some(config_path.to_path_buf());
+    Ok(config)
+}
+
+/// Replaces `{var:NAME}` placeholders in the command, args, cwd and env values
+/// with the entries of `[vars]`, `{env:NAME}` with the launcher's own
+/// environment (empty if unset) and `{launch_id}` with the launch id. Unknown
+/// `vars` names are left as they are.
+fn expand_vars(config: &mut Config) {
+    let vars = &config.vars;
+    let expand = |text: &mut String| {
+        if text.contains(LAUNCH_ID_PLACEHOLDER) {
+            *text = text.replace(LAUNCH_ID_PLACEHOLDER, launch_id());
+        }
+        for (name, value) in vars {
+            let placeholder = format!("{{var:{}}}", name);
+            if text.contains(&placeholder) {
+                *text = text.replace(&placeholder, value);
+            }
+        }
+        // Values are not expanded again, so scanning goes on after them.
+        let mut from = 0;
+        while let Some(start) = text[from..].find(ENV_PLACEHOLDER).map(|start| from + start) {
+            let Some(len) = text[start..].find('}') else {
+                break;
+            };
+            let name = &text[start + ENV_PLACEHOLDER.len()..start + len];
+            let value = std::env::var(name).unwrap_or_default();
+            text.replace_range(start..=start + len, &value);
+            from = start + value.len();
+        }
+    };
+    let expand_path = |path: &mut PathBuf| {
+        let mut text = path.to_string_lossy().into_owned();
+        expand(&mut text);
+        *path = PathBuf::from(text);
+    };
+    match &mut config.command {
+        CommandConfig::Path(path) => expand_path(path),
+        CommandConfig::App { aumid } => expand(aumid),
+    }
+    if let Some(cwd) = &mut config.cwd {
+        expand_path(cwd);
+    }
+    config.args.iter_mut().for_each(expand);
+    for env in config.env.values_mut() {
+        match env {
+            EnvConfig::Simple(value)
+            | EnvConfig::Checked { value, .. }
+            | EnvConfig::IfUnset { if_unset: value } => expand(value),
+            EnvConfig::Detailed {
+                append, prepend, ..
+            } => append.iter_mut().chain(prepend.iter_mut()).for_each(expand),
+            EnvConfig::From { .. }
+            | EnvConfig::Vault { .. }
+            | EnvConfig::Http(_)
+            | EnvConfig::File { .. }
+            | EnvConfig::Credential { .. }
+            | EnvConfig::Unset { .. } => {}
+            EnvConfig::FromCommand { from_command } => from_command.iter_mut().for_each(expand),
+        }
+    }
+}
+
+/// Resolves a relative path from the config against the config file's
+/// directory, unless `relative_to = "cwd"`.
+fn config_relative(config: &Config, path: &Path) -> PathBuf {
+    match config.path.as_deref().and_then(Path::parent) {
+        Some(config_dir) if config.relative_to != Some(RelativeTo::Cwd) => {
+            let path = config_dir.join(path);
+            std::path::absolute(&path).unwrap_or(path)
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+/// With `relative_to = "config"`, resolves the command if it is a relative
+/// path rather than a bare name looked up in PATH.
+fn anchor_command(config: &Config, program: String) -> String {
+    let path = Path::new(&program);
+    if config.relative_to != Some(RelativeTo::Config)
+        || config.shell.and_then(script::ShellConfig::shell).is_some()
+        || path.is_absolute()
+        || path.components().count() < 2
+    {
+        return program;
+    }
+    config_relative(config, path).to_string_lossy().into_owned()
+}
+
+/// With `relative_to = "config"`, resolves env values written as relative
+/// paths, such as `./lib` or `..\shared`.
+fn anchor_value(config: &Config, value: String) -> String {
+    let relative = [".", ".."].contains(&value.as_str())
+        || ["./", "../", ".\\", "..\\"]
+            .iter()
+            .any(|prefix| value.starts_with(prefix));
+    if config.relative_to != Some(RelativeTo::Config) || !relative {
+        return value;
+    }
+    config_relative(config, Path::new(&value))
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn wait_before_spawn(delay: Duration, message: Option<&str>, countdown: bool) {
+    if let Some(message) = message {
+        eprintln!("{}", message);
+    }
+    if !countdown {
+        std::thread::sleep(delay);
+        return;
+    }
+    let deadline = Instant::now() + delay;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        let seconds = remaining.as_millis().div_ceil(1000);
+        eprint!("\rstarting in {}s ", seconds);
+        std::thread::sleep(remaining.min(Duration::from_secs(1)));
+    }
+    eprintln!();
+}
+
+/// Random id of this launcher invocation, passed to the command as LAUNCH_ID.
+fn launch_id() -> &'static str {
+    static LAUNCH_ID: OnceLock<String> = OnceLock::new();
+    LAUNCH_ID.get_or_init(|| format!("{:016x}", RandomState::new().build_hasher().finish()))
+}
+
+fn random_duration(max: Duration) -> Duration {
+    if max.is_zero() {
+        return max;
+    }
+    let random = RandomState::new().build_hasher().finish();
+    Duration::from_nanos(random % max.as_nanos() as u64)
+}
+
+/// Returns a description of the first rule `value` breaks, if any.
+fn check_env_value(
+    name: &str,
+    value: &str,
+    must_match: Option<&str>,
+    must_be_dir: bool,
+) -> anyhow::Result<Option<String>> {
+    if let Some(pattern) = must_match {
+        let regex = regex::Regex::new(pattern).map_err(|error| {
+            anyhow::Error::new(error).context(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("parse must_match pattern of {}", name),
+            ))
+        })?;
+        if !regex.is_match(value) {
+            return Ok(Some(format!(
+                "{}={} does not match {}",
+                name, value, pattern
+            )));
+        }
+    }
+    if must_be_dir && !Path::new(value).is_dir() {
+        return Ok(Some(format!("{}={} is not a directory", name, value)));
+    }
+    Ok(None)
+}
+
+/// Whether two variable names refer to the same variable, ignoring case on
+/// Windows. Setting a variable there keeps the casing of the name it already
+/// has, so `Path` in the config updates an inherited `PATH`.
+fn same_env_name(a: &OsStr, b: &OsStr) -> bool {
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// Value of `name` as the command would see it with the variables set so far.
+fn current_var(
+    command: &Command,
+    name: &str,
+    inherited_var: &impl Fn(&str) -> Option<OsString>,
+) -> Option<OsString> {
+    match command
+        .get_envs()
+        .find(|(env_name, _)| same_env_name(env_name, name.as_ref()))
+    {
+        Some((_, value)) => value.map(OsStr::to_os_string),
+        None => inherited_var(name),
+    }
+}
+
+/// Drops the empty entries of a `sep` separated list and, as requested, makes
+/// the entries absolute paths and removes repeated ones.
+fn clean_list(value: &str, sep: &str, dedup: bool, normalize: bool) -> String {
+    let same_entry = |a: &str, b: &str| {
+        if cfg!(windows) {
+            a.eq_ignore_ascii_case(b)
+        } else {
+            a == b
+        }
+    };
+    let mut entries: Vec<String> = Vec::new();
+    for entry in value.split(sep).filter(|entry| !entry.is_empty()) {
+        let entry = if normalize {
+            let path = std::path::absolute(entry).unwrap_or_else(|_| PathBuf::from(entry));
+            let path = path.to_string_lossy();
+            let trimmed = path.trim_end_matches(['/', '\\']);
+            // Keep the separator of a root such as `/` or `C:\`.
+            if trimmed.is_empty() || trimmed.ends_with(':') {
+                path.to_string()
+            } else {
+                trimmed.to_string()
+            }
+        } else {
+            entry.to_string()
+        };
+        if !dedup || !entries.iter().any(|existing| same_entry(existing, &entry)) {
+            entries.push(entry);
+        }
+    }
+    entries.join(sep)
+}
+
+/// Runs a `from_command` helper with the environment collected in
+/// `environment` and returns its trimmed stdout.
+fn command_output(
+    config: &Config,
+    environment: &Command,
+    replace_environment: bool,
+    command_line: &[String],
+) -> anyhow::Result<String> {
+    let (program, args) = command_line
+        .split_first()
+        .context("from_command is empty")?;
+    let program = Path::new(program);
+    let mut command = script::command(program, args);
+    if replace_environment {
+        command.env_clear();
+    }
+    for (name, value) in environment.get_envs() {
+        match value {
+            Some(value) => command.env(name, value),
+            None => command.env_remove(name),
+        };
+    }
+    if let Some(cwd) = &config.cwd {
+        command.current_dir(config_relative(config, cwd));
+    }
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|error| spawn_error(error, program))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{} exited with {}",
+            program.display(),
+            exit_status::describe(output.status)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Whether the command gets an explicit environment instead of the launcher's
+/// own plus changes.
+fn replaces_environment(config: &Config) -> bool {
+    config.inherit_from_pid.is_some()
+        || config.reload_system_env
+        || config.normalize_names == NameCase::Upper
+        || config.env_clear
+        || !config.env_remove.is_empty()
+}
+
+fn build_command(config: &Config) -> anyhow::Result<Command> {
+    build_command_with(config, true)
+}
+
+/// Builds the command, leaving out the variables read from vaults, over
+/// http, from credentials and from helper commands unless `dynamic` is set,
+/// for checks that must not contact anything or run anything.
+fn build_command_with(config: &Config, dynamic: bool) -> anyhow::Result<Command> {
+    // Collects the environment first, so the command line can refer to it.
+    let mut command = Command::new("");
+    if config.reload_system_env && config.inherit_from_pid.is_some() {
+        return Err(Coded::new(
+            ErrorCode::ConfigInvalid,
+            "reload_system_env and inherit_from_pid both replace the base environment",
+        )
+        .into());
+    }
+    let inherited = match &config.inherit_from_pid {
+        Some(process) => {
+            let pid = match process {
+                ProcessRef::Pid(pid) => *pid,
+                ProcessRef::Name(name) => processes::find_by_name(name)?
+                    .with_context(|| format!("no running process named {}", name))?,
+            };
+            verbose::log(format_args!(
+                "starting from the environment of process {}",
+                pid
+            ));
+            Some(processes::environment_of(pid)?)
+        }
+        None if config.reload_system_env => {
+            verbose::log(format_args!("starting from the system environment"));
+            Some(system_env::load()?)
+        }
+        None => None,
+    };
+    let passed_on = |name: &OsStr| {
+        let listed = |names: &[String]| {
+            names
+                .iter()
+                .any(|listed| same_env_name(name, listed.as_ref()))
+        };
+        (!config.env_clear || listed(&config.env_keep)) && !listed(&config.env_remove)
+    };
+    let inherited_var = |name: &str| {
+        if !passed_on(name.as_ref()) {
+            return None;
+        }
+        match &inherited {
+            Some(inherited) => inherited
+                .iter()
+                .find(|(inherited_name, _)| same_env_name(inherited_name, name.as_ref()))
+                .map(|(_, value)| value.clone()),
+            None => std::env::var_os(name),
+        }
+    };
+    let replace_environment = replaces_environment(config);
+    if replace_environment {
+        // Windows keeps the casing of an inherited name when it is set again,
+        // so the whole environment is passed on explicitly.
+        command.env_clear();
+        let base = match &inherited {
+            Some(inherited) => inherited.clone(),
+            None => std::env::vars_os().collect(),
+        };
+        for (name, value) in base.into_iter().filter(|(name, _)| passed_on(name)) {
+            match config.normalize_names {
+                NameCase::Upper => command.env(name.to_ascii_uppercase(), value),
+                NameCase::Preserve => command.env(name, value),
+            };
+        }
+    }
+    command.env(LAUNCH_ID_VAR, launch_id());
+    if config.utf8_console {
+        // Hints for runtimes that pick their encoding independently of the console.
+        command.env("PYTHONUTF8", "1");
+        command.env("PYTHONIOENCODING", "utf-8");
+    }
+    let normalize = |env_name: &str| match config.normalize_names {
+        NameCase::Upper => env_name.to_ascii_uppercase(),
+        NameCase::Preserve => env_name.to_string(),
+    };
+    for env_file in &config.env_files {
+        verbose::log(format_args!("loading env file {}", env_file.display()));
+        for variable in dotenv::load(&config_relative(config, env_file))? {
+            let value = if variable.literal {
+                variable.value
+            } else {
+                expand::expand(&variable.value, |name| {
+                    current_var(&command, name, &inherited_var)
+                })
+            };
+            command.env(normalize(&variable.name), value);
+        }
+    }
+    let mut vault_client = None;
+    for (env_name, env) in &config.env {
+        let env_name = &normalize(env_name);
+        if !dynamic && env.is_dynamic() {
+            verbose::log(format_args!("env {} left out", env_name));
+            continue;
+        }
+        let expand = |value: &str| {
+            anchor_value(
+                config,
+                expand::expand(value, |name| current_var(&command, name, &inherited_var)),
+            )
+        };
+        match env {
+            EnvConfig::Simple(value) => {
+                let value = expand(value);
+                verbose::log(format_args!("env {} = {}", env_name, value));
+                command.env(env_name, value);
+            }
+            EnvConfig::Checked {
+                value,
+                must_match,
+                must_be_dir,
+                warn_only,
+            } => {
+                let value = &expand(value);
+                if let Some(violation) =
+                    check_env_value(env_name, value, must_match.as_deref(), *must_be_dir)?
+                {
+                    if !*warn_only {
+                        return Err(Coded::new(ErrorCode::ConfigInvalid, violation).into());
+                    }
+                    eprintln!("warning: {}", violation);
+                }
+                verbose::log(format_args!("env {} = {}", env_name, value));
+                command.env(env_name, value);
+            }
+            EnvConfig::From { from, transform } => {
+                if let Some(value) = current_var(&command, from, &inherited_var) {
+                    let value = transform
+                        .iter()
+                        .fold(value.to_string_lossy().into_owned(), |value, step| {
+                            step.apply(value)
+                        });
+                    verbose::log(format_args!("env {} = {} from {}", env_name, value, from));
+                    command.env(env_name, value);
+                } else {
+                    verbose::log(format_args!(
+                        "env {} skipped, {} is not set",
+                        env_name, from
+                    ));
+                }
+            }
+            EnvConfig::Detailed {
+                append,
+                prepend,
+                sep,
+                dedup,
+                normalize,
+            } => {
+                let sep = match sep.as_deref() {
+                    None | Some("path") => PATH_SEP,
+                    Some(sep) => sep,
+                };
+                let join = |values: &[String]| {
+                    values
+                        .iter()
+                        .map(|value| expand(value))
+                        .collect::<Vec<_>>()
+                        .join(sep)
+                        .to_os_string()
+                };
+                let prepend = join(prepend);
+                let append = join(append);
+                // Also what an env file or an entry above set it to.
+                let origin = current_var(&command, env_name, &inherited_var).unwrap_or_default();
+                let mut value = prepend;
+                if !origin.is_empty() {
+                    if !value.is_empty() {
+                        value.push(sep);
+                    }
+                    value.push(&origin);
+                }
+                if !append.is_empty() {
+                    if !value.is_empty() {
+                        value.push(sep);
+                    }
+                    value.push(&append);
+                }
+                if *dedup || *normalize {
+                    value = clean_list(&value.to_string_lossy(), sep, *dedup, *normalize).into();
+                }
+                verbose::log(format_args!(
+                    "env {} = {}",
+                    env_name,
+                    value.to_string_lossy()
+                ));
+                command.env(env_name, value);
+            }
+            EnvConfig::Vault { vault } => {
+                if vault_client.is_none() {
+                    vault_client =
+                        Some(vault::VaultClient::from_env().context("connect to vault")?);
+                }
+                let value = vault_client.as_ref().unwrap().read(vault)?;
+                verbose::hide(&value);
+                verbose::log(format_args!("env {} read from vault", env_name));
+                command.env(env_name, value);
+            }
+            EnvConfig::Http(source) => {
+                let value = source.fetch()?;
+                verbose::hide(&value);
+                command.env(env_name, value);
+                verbose::log(format_args!("env {} fetched over http", env_name));
+            }
+            EnvConfig::File { file } => {
+                let path = config_relative(config, file);
+                let value = std::fs::read_to_string(&path)
+                    .with_context(|| format!("read {} for {}", path.display(), env_name))?;
+                let value = value.strip_suffix('\n').unwrap_or(&value);
+                let value = value.strip_suffix('\r').unwrap_or(value);
+                verbose::hide(value);
+                command.env(env_name, value);
+                verbose::log(format_args!(
+                    "env {} read from {}",
+                    env_name,
+                    path.display()
+                ));
+            }
+            EnvConfig::Credential { credential } => {
+                let value = credential::read(credential)?;
+                verbose::hide(&value);
+                command.env(env_name, value);
+                verbose::log(format_args!(
+                    "env {} read from credential {}",
+                    env_name, credential
+                ));
+            }
+            EnvConfig::FromCommand { from_command } => {
+                let value = command_output(config, &command, replace_environment, from_command)
+                    .with_context(|| format!("get {} from command", env_name))?;
+                verbose::hide(&value);
+                verbose::log(format_args!("env {} set from command", env_name));
+                command.env(env_name, value);
+            }
+            EnvConfig::IfUnset { if_unset } => {
+                if current_var(&command, env_name, &inherited_var).is_none() {
+                    let value = expand(if_unset);
+                    verbose::log(format_args!("env {} = {}", env_name, value));
+                    command.env(env_name, value);
+                } else {
+                    verbose::log(format_args!("env {} is already set, kept", env_name));
+                }
+            }
+            EnvConfig::Unset { unset } => {
+                if *unset {
+                    verbose::log(format_args!("env {} removed", env_name));
+                    command.env_remove(env_name);
+                }
+            }
+        }
+    }
+
+    let lookup = |name: &str| current_var(&command, name, &inherited_var);
+    let program = anchor_command(
+        config,
+        expand::expand(&config.command.as_path().to_string_lossy(), lookup),
+    );
+    let shell = config.shell.and_then(script::ShellConfig::shell);
+    // A bare name is looked up on the PATH set up above, not the launcher's.
+    let program = match lookup("PATH") {
+        Some(path) if shell.is_none() && Path::new(&program).components().count() == 1 => {
+            which::find(Path::new(&program), Some(&path))
+                .and_then(|found| std::path::absolute(found).ok())
+                .map_or(program, |found| found.to_string_lossy().into_owned())
+        }
+        _ => program,
+    };
+    let args: Vec<String> = config
+        .args
+        .iter()
+        .map(|arg| expand::expand(arg, lookup))
+        .collect();
+    let cwd = config.cwd.as_ref().map(|cwd| {
+        config_relative(
+            config,
+            Path::new(&expand::expand(&cwd.to_string_lossy(), lookup)),
+        )
+    });
+    let environment = command;
+    let mut command = match shell {
+        Some(shell) => script::shell_command(shell, &program, &args),
+        None => script::command(Path::new(&program), &args),
+    };
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+    if replace_environment {
+        command.env_clear();
+    }
+    for (name, value) in environment.get_envs() {
+        match value {
+            Some(value) => command.env(name, value),
+            None => command.env_remove(name),
+        };
+    }
+
+    if config.detach {
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::null());
+        command.stderr(Stdio::null());
+    }
+    set_process_flags(&mut command, config);
+    Ok(command)
+}
+
+#[cfg(windows)]
+fn set_process_flags(command: &mut Command, config: &Config) {
+    let mut creation_flags = 0;
+    if config.detach {
+        creation_flags |= DETACHED_PROCESS;
+    }
+    if config.new_process_group {
+        creation_flags |= CREATE_NEW_PROCESS_GROUP;
+    }
+    if config.efficiency_mode {
+        creation_flags |= IDLE_PRIORITY_CLASS;
+    }
+    if config.hide_console {
+        creation_flags |= CREATE_NO_WINDOW;
+    }
+    command.creation_flags(creation_flags);
+}
+
+#[cfg(unix)]
+fn set_process_flags(command: &mut Command, config: &Config) {
+    if let Some(arg0) = &config.arg0 {
+        command.arg0(arg0);
+    }
+    if config.detach {
+        detach(command);
+    } else if config.new_process_group {
+        command.process_group(0);
+    }
+}
+
+/// Runs the command in a new session so it is not tied to the launcher's
+/// terminal.
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Waits for the child, killing it once its heartbeat file hasn't been
+/// touched for `stale_after` or once it runs past `timeout`, and asking it to
+/// exit once a watched file changes.
+fn wait_for_child(child: &mut Child, config: &Config) -> anyhow::Result<WaitEnd> {
+    let deadline = config.timeout.map(|timeout| Instant::now() + timeout);
+    let watcher = watch::Watcher::new(watched_paths(config));
+    if config.watchdog.is_none() && deadline.is_none() && watcher.is_empty() {
+        return Ok(WaitEnd::Exited(
+            child.wait().context("wait for child process")?,
+        ));
+    }
+    let started = SystemTime::now();
+    let heartbeat = config
+        .watchdog
+        .as_ref()
+        .map(|watchdog| config_relative(config, &watchdog.file));
+    let poll_interval = config
+        .watchdog
+        .as_ref()
+        .map_or(Duration::from_millis(100), |watchdog| {
+            (watchdog.stale_after / 4).clamp(Duration::from_millis(100), Duration::from_secs(1))
+        });
+    loop {
+        if let Some(status) = child.try_wait().context("wait for child process")? {
+            return Ok(WaitEnd::Exited(status));
+        }
+        if let (Some(watchdog), Some(heartbeat)) = (&config.watchdog, &heartbeat) {
+            let last_beat = std::fs::metadata(heartbeat)
+                .and_then(|metadata| metadata.modified())
+                .map_or(started, |modified| modified.max(started));
+            if last_beat.elapsed().unwrap_or_default() > watchdog.stale_after {
+                child.kill().context("kill stale child process")?;
+                child.wait().context("wait for child process")?;
+                return Ok(WaitEnd::StaleHeartbeat);
+            }
+        }
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Ok(WaitEnd::TimedOut(stop_child(
+                child,
+                config,
+                config.on_timeout,
+            )?));
+        }
+        if let Some(path) = watcher.changed() {
+            let path = path.to_path_buf();
+            stop_child(child, config, TimeoutAction::Terminate)?;
+            return Ok(WaitEnd::Changed(path));
+        }
+        std::thread::sleep(poll_interval);
+    }
+}
+
+/// The `[watch]` files, and the config file with `--watch`.
+fn watched_paths(config: &Config) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = config
+        .watch
+        .iter()
+        .flat_map(|watch| &watch.paths)
+        .map(|path| config_relative(config, path))
+        .collect();
+    if config.watch_config {
+        paths.extend(config.path.clone());
+    }
+    paths
+}
+
+/// Ends a child that ran past its timeout or is to be restarted.
+fn stop_child(
+    child: &mut Child,
+    config: &Config,
+    action: TimeoutAction,
+) -> anyhow::Result<ExitStatus> {
+    if action == TimeoutAction::Terminate {
+        match processes::terminate(child.id(), config.new_process_group) {
+            Ok(()) => {
+                let deadline = Instant::now() + TERMINATE_GRACE;
+                while Instant::now() < deadline {
+                    if let Some(status) = child.try_wait().context("wait for child process")? {
+                        return Ok(status);
+                    }
+                    std::thread::sleep(Duration::from_millis(100));
+                }
+            }
+            Err(error) => eprintln!("warning: {:?}", error),
+        }
+    }
+    child.kill().context("kill child process")?;
+    child.wait().context("wait for child process")
+}
+
+/// Spawns the configured command and, if `wait` is set, waits for it.
+fn run_once(config: &Config, wait: bool, pipes: &PipeEnds) -> anyhow::Result<Option<ExitStatus>> {
+    if let CommandConfig::App { aumid } = &config.command {
+        return run_app(config, aumid, wait);
+    }
+    if let Some(run_as) = &config.run_as {
+        return run_as_user(config, run_as, wait);
+    }
+    loop {
+        let _instance_slot = match config.max_instances {
+            Some(max_instances) => Some(instances::InstanceSlot::acquire(
+                config.path.as_deref().unwrap_or(config.command.as_path()),
+                max_instances,
+                config.instance_wait,
+            )?),
+            None => None,
+        };
+        let mut command = build_command(config)?;
+        if config.exec && wait {
+            exec(config, &mut command)?;
+        }
+        let _code_page = (wait && config.utf8_console).then(console::Utf8CodePage::set);
+        let _keep_awake = config
+            .keep_awake
+            .filter(|_| wait)
+            .map(power::KeepAwakeGuard::set);
+        let capture = wait && (config.debug_output || config.output_encoding.is_some());
+        if capture {
+            command.stdout(Stdio::piped());
+            command.stderr(Stdio::piped());
+        }
+        let mut sinks = redirect_streams(config, &mut command, capture)?;
+        if let Some(stdin) = &pipes.stdin {
+            command.stdin(stdin.try_clone().context("share pipe")?);
+        }
+        if let Some(stdout) = &pipes.stdout {
+            let stdout = stdout.try_clone().context("share pipe")?;
+            if capture {
+                sinks[0] = output::Sink::Pipe(stdout);
+            } else {
+                command.stdout(stdout);
+            }
+        }
+        let early_stderr = match config.startup_grace {
+            Some(_) if !wait && config.stderr.is_none() => {
+                Some(startup::EarlyStderr::capture(&mut command)?)
+            }
+            _ => None,
+        };
+        let kill_on_exit = config.kill_on_exit && wait;
+        #[cfg(unix)]
+        if kill_on_exit {
+            job::kill_with_launcher(&mut command);
+        }
+        verbose::command(&command);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(error)
+                if config.elevate.is_some_and(elevate::Elevate::if_needed)
+                    && elevate::required(&error) =>
+            {
+                return Err(elevate::Relaunched(elevate::relaunch_self(wait)?).into());
+            }
+            Err(error) => return Err(spawn_error(error, config.command.as_path())),
+        };
+        if early_stderr.is_some() {
+            // Close the launcher's handle to the file, the command has its own.
+            command.stderr(Stdio::null());
+        }
+        let _job = if config.ui_limits.is_some() || kill_on_exit {
+            match create_job(config, kill_on_exit, &child) {
+                Ok(job) => Some(job),
+                Err(error) => {
+                    // Don't leave the child running without its limits.
+                    let _ = child.kill();
+                    return Err(error);
+                }
+            }
+        } else {
+            None
+        };
+        if config.efficiency_mode {
+            if let Err(error) = processes::enable_efficiency_mode(&child) {
+                eprintln!("warning: {:?}", error);
+            }
+        }
+        if let Some(priority) = config.priority {
+            if let Err(error) = priority::set_priority(&child, priority) {
+                eprintln!("warning: {:?}", error);
+            }
+        }
+        if !config.affinity.is_empty() {
+            if let Err(error) = priority::set_affinity(&child, &config.affinity) {
+                eprintln!("warning: {:?}", error);
+            }
+        }
+        trace::spawn(config.command.as_path(), child.id());
+        let _pid_file = match &config.pid_file {
+            Some(pid_file) => {
+                let path = config_relative(config, pid_file.path());
+                let environment = resolved_environment(config, &command);
+                pid_file.write(&path, child.id(), launch_id(), &command, &environment)?;
+                wait.then_some(pidfile::RemoveOnDrop(path))
+            }
+            None => None,
+        };
+        let mut report = config.report.as_ref().map(|path| {
+            // Placeholders in the path are kept as they are.
+            let path = config_relative(config, Path::new(path));
+            let report = report::Report::new(&command, child.id(), launch_id());
+            (path.to_string_lossy().into_owned(), report)
+        });
+        if let Some(lifetime) = config.lifetime.as_ref().filter(|_| wait) {
+            follow_lifetime(lifetime, child.id())?;
+        }
+        if !wait {
+            if let Some(grace) = config.startup_grace {
+                check_startup(config, &mut child, grace, early_stderr.as_ref())?;
+            }
+            if let Some((path, report)) = &report {
+                report.write(path)?;
+            }
+            return Ok(None);
+        }
+        let _signals = signals::Forwarder::install(
+            child.id(),
+            config.new_process_group || (cfg!(unix) && config.detach),
+            config.kill_on_terminate,
+        );
+        let forwarders = if capture {
+            output::forward(
+                &mut child,
+                sinks,
+                config.output_encoding,
+                config.debug_output,
+            )
+        } else {
+            Vec::new()
+        };
+        let end = wait_for_child(&mut child, config)?;
+        let status = match end {
+            WaitEnd::Exited(status) | WaitEnd::TimedOut(status) => Some(status),
+            WaitEnd::StaleHeartbeat | WaitEnd::Changed(_) => None,
+        };
+        for forwarder in forwarders {
+            let _ = forwarder.join();
+        }
+        if let Some((path, report)) = &mut report {
+            report.finish(status);
+            report.write(path)?;
+        }
+        if let WaitEnd::TimedOut(status) = end {
+            trace::exit(child.id(), exit_status::code(status));
+            return Err(Coded::new(
+                ErrorCode::Timeout,
+                format!(
+                    "{} timed out after {}",
+                    config.command.as_path().display(),
+                    humantime::format_duration(config.timeout.unwrap_or_default())
+                ),
+            )
+            .into());
+        }
+        if let WaitEnd::Changed(path) = end {
+            if config.watch_config && config.path.as_ref() == Some(&path) {
+                return Err(watch::ConfigChanged(path).into());
+            }
+            trace::restart(config.command.as_path(), "watched file changed");
+            eprintln!(
+                "{} changed, restarting {}",
+                path.display(),
+                config.command.as_path().display()
+            );
+            continue;
+        }
+        if let Some(status) = status {
+            trace::exit(child.id(), exit_status::code(status));
+            if !succeeded(config, status) && !config.on_crash.is_empty() {
+                if let Err(error) = run_on_crash(config, child.id(), status) {
+                    eprintln!("warning: {:?}", error);
+                }
+            }
+            return Ok(Some(status));
+        }
+        trace::restart(config.command.as_path(), "stale heartbeat");
+        eprintln!(
+            "heartbeat of {} is stale, restarting it",
+            config.command.as_path().display()
+        );
+    }
+}
+
+/// Fails unless the detached child is still running after `grace` or exited
+/// successfully before, with its early stderr as the cause.
+fn check_startup(
+    config: &Config,
+    child: &mut Child,
+    grace: Duration,
+    early_stderr: Option<&startup::EarlyStderr>,
+) -> anyhow::Result<()> {
+    let Some(status) = startup::watch(child, grace)? else {
+        if let Some(early_stderr) = early_stderr {
+            verbose::log(format_args!(
+                "stderr goes to {}",
+                early_stderr.path().display()
+            ));
+        }
+        return Ok(());
+    };
+    trace::exit(child.id(), exit_status::code(status));
+    let stderr = early_stderr
+        .map(|early_stderr| {
+            let stderr = early_stderr.read();
+            early_stderr.remove();
+            stderr
+        })
+        .unwrap_or_default();
+    if succeeded(config, status) {
+        return Ok(());
+    }
+    if let Some(pid_file) = &config.pid_file {
+        let _ = std::fs::remove_file(config_relative(config, pid_file.path()));
+    }
+    let failure = Coded::new(
+        ErrorCode::SpawnFailed,
+        format!(
+            "{} exited with {} within its startup grace of {}",
+            config.command.as_path().display(),
+            exit_status::describe(status),
+            humantime::format_duration(grace)
+        ),
+    );
+    if stderr.is_empty() {
+        return Err(failure.into());
+    }
+    Err(anyhow::Error::msg(stderr).context(failure))
+}
+
+/// Replaces the launcher with the command, which keeps the launcher's process
+/// id, signals and exit code. Only returns if starting the command failed.
+#[cfg(unix)]
+fn exec(config: &Config, command: &mut Command) -> anyhow::Result<()> {
+    redirect_streams(config, command, false)?;
+    trace::spawn(config.command.as_path(), std::process::id());
+    verbose::command(command);
+    let error = command.exec();
+    Err(spawn_error(error, config.command.as_path()))
+}
+
+#[cfg(windows)]
+fn exec(_config: &Config, _command: &mut Command) -> anyhow::Result<()> {
+    eprintln!("warning: exec is only supported on Unix");
+    Ok(())
+}
+
+/// Points the command's streams at the configured files. With `capture` the
+/// command's output stays piped and the sinks it should be forwarded to are
+/// returned instead.
+fn redirect_streams(
+    config: &Config,
+    command: &mut Command,
+    capture: bool,
+) -> anyhow::Result<[output::Sink; 2]> {
+    if let Some(stdin) = &config.stdin {
+        if stdin == Path::new(logfile::NULL) {
+            command.stdin(Stdio::null());
+        } else {
+            let path = config_relative(config, stdin);
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("open stdin file {}", path.display()))?;
+            command.stdin(file);
+        }
+    }
+    let stdout_null = config
+        .stdout
+        .as_ref()
+        .is_some_and(|log| log.is(logfile::NULL));
+    let stdout_path = config
+        .stdout
+        .as_ref()
+        .filter(|_| !stdout_null)
+        .map(|log| config_relative(config, log.path()));
+    let mut stdout_file = None;
+    let stdout = if stdout_null {
+        Some(output::Sink::Null)
+    } else if let (Some(log), Some(path)) = (&config.stdout, &stdout_path) {
+        let file = log.open(path)?;
+        let sink = output::Sink::File(file.try_clone().context("share log file")?);
+        stdout_file = Some(file);
+        Some(sink)
+    } else {
+        None
+    };
+    let stderr = match &config.stderr {
+        None => None,
+        Some(log) if log.is(logfile::NULL) => Some(output::Sink::Null),
+        Some(log) if log.is(logfile::STDOUT) => Some(match stdout_file {
+            Some(file) => output::Sink::File(file),
+            None if stdout_null || config.detach => output::Sink::Null,
+            None => output::Sink::Stdout,
+        }),
+        Some(log) => {
+            let path = config_relative(config, log.path());
+            let file = match stdout_file {
+                // Opening the file twice would have the streams overwrite each other.
+                Some(file) if stdout_path.as_ref() == Some(&path) => file,
+                _ => log.open(&path)?,
+            };
+            Some(output::Sink::File(file))
+        }
+    };
+    if capture {
+        return Ok([
+            stdout.unwrap_or(output::Sink::Stdout),
+            stderr.unwrap_or(output::Sink::Stderr),
+        ]);
+    }
+    if let Some(stdout) = stdout {
+        command.stdout(stdio(stdout));
+    }
+    if let Some(stderr) = stderr {
+        command.stderr(stdio(stderr));
+    }
+    Ok([output::Sink::Stdout, output::Sink::Stderr])
+}
+
+/// The stream a command writes to `sink` through directly.
+fn stdio(sink: output::Sink) -> Stdio {
+    match sink {
+        output::Sink::Stdout => {
+            // A launcher without a console has no stdout to write to.
+            launcher_stdout().map_or(Stdio::null(), Stdio::from)
+        }
+        output::Sink::Stderr => Stdio::inherit(),
+        output::Sink::File(file) => Stdio::from(file),
+        output::Sink::Pipe(pipe) => Stdio::from(pipe),
+        output::Sink::Null => Stdio::null(),
+    }
+}
+
+/// A copy of the launcher's own stdout, for a command to write to.
+#[cfg(unix)]
+fn launcher_stdout() -> io::Result<std::os::fd::OwnedFd> {
+    use std::os::fd::AsFd;
+    io::stdout().as_fd().try_clone_to_owned()
+}
+
+#[cfg(windows)]
+fn launcher_stdout() -> io::Result<std::os::windows::io::OwnedHandle> {
+    use std::os::windows::io::AsHandle;
+    io::stdout().as_handle().try_clone_to_owned()
+}
+
+/// Places the child in a job object with the configured limits.
+fn create_job(config: &Config, kill_on_exit: bool, child: &Child) -> anyhow::Result<job::Job> {
+    let job = job::Job::create()?;
+    if let Some(limits) = &config.ui_limits {
+        job.set_ui_limits(limits)?;
+    }
+    if kill_on_exit {
+        job.set_kill_on_close()?;
+    }
+    job.assign(child)?;
+    Ok(job)
+}
+
+/// Runs the `on_crash` hook with the child's PID and exit code in
+/// LAUNCHER_CHILD_PID and LAUNCHER_EXIT_CODE.
+fn run_on_crash(config: &Config, pid: u32, status: ExitStatus) -> anyhow::Result<()> {
+    let program = Path::new(&config.on_crash[0]);
+    let mut hook = script::command(program, &config.on_crash[1..]);
+    if let Some(cwd) = &config.cwd {
+        hook.current_dir(config_relative(config, cwd));
+    }
+    hook.env("LAUNCHER_CHILD_PID", pid.to_string())
+        .env("LAUNCHER_EXIT_CODE", exit_status::code(status).to_string())
+        .status()
+        .map_err(|error| spawn_error(error, program))?;
+    Ok(())
+}
+
+/// Whether the program named in `skip_if_running` is already running, focusing
+/// its window if asked to.
+fn already_running(skip: &SkipIfRunningConfig) -> anyhow::Result<bool> {
+    let (process, window_class, focus) = match skip {
+        SkipIfRunningConfig::Process(process) => (Some(process), None, false),
+        SkipIfRunningConfig::Detailed {
+            process,
+            window_class,
+            focus,
+        } => (process.as_ref(), window_class.as_ref(), *focus),
+    };
+    if let Some(hwnd) = window_class.and_then(|class| window::find_by_class(class)) {
+        if focus {
+            window::focus(hwnd);
+        }
+        return Ok(true);
+    }
+    let pid = match process {
+        Some(process) => processes::find_by_name(process)?,
+        None => None,
+    };
+    if let Some(pid) = pid {
+        if let Some(hwnd) = window::find_by_pid(pid).filter(|_| focus) {
+            window::focus(hwnd);
+        }
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Brings the window of a running instance of the command to the foreground.
+fn focus_running(config: &Config) -> anyhow::Result<()> {
+    let Some(exe_name) = config.command.as_path().file_name() else {
+        return Ok(());
+    };
+    let pid = processes::find_by_name(&exe_name.to_string_lossy())?;
+    if let Some(hwnd) = pid.and_then(window::find_by_pid) {
+        window::focus(hwnd);
+    }
+    Ok(())
+}
+
+fn follow_lifetime(lifetime: &LifetimeConfig, child: u32) -> anyhow::Result<()> {
+    let followed = match &lifetime.follow_pid_env {
+        Some(name) => std::env::var(name)
+            .ok()
+            .and_then(|pid| pid.trim().parse().ok())
+            .with_context(|| format!("{} does not hold a process id", name))?,
+        None if lifetime.follow_parent => processes::parent_pid()?,
+        None => return Ok(()),
+    };
+    processes::kill_when_exits(followed, child)
+}
+
+fn succeeded(config: &Config, status: ExitStatus) -> bool {
+    if config.success_codes.is_empty() {
+        return status.success();
+    }
+    config.success_codes.contains(&exit_status::code(status))
+}
+
+fn spawn_error(error: io::Error, program: &Path) -> anyhow::Error {
+    let code = match error.kind() {
+        io::ErrorKind::NotFound => ErrorCode::CommandNotFound,
+        _ => ErrorCode::SpawnFailed,
+    };
+    anyhow::Error::new(error).context(Coded::new(
+        code,
+        format!("spawn process {}", program.display()),
+    ))
+}
+
+/// Builds a command for `program` that gets the environment and working
+/// directory the configured command would get.
+fn command_in_environment(
+    config: &Config,
+    program: &Path,
+    args: &[String],
+) -> anyhow::Result<Command> {
+    let main_command = build_command(config)?;
+    let mut command = script::command(program, args);
+    if let Some(cwd) = main_command.get_current_dir() {
+        command.current_dir(cwd);
+    }
+    if replaces_environment(config) {
+        command.env_clear();
+    }
+    for (name, value) in main_command.get_envs() {
+        match value {
+            Some(value) => command.env(name, value),
+            None => command.env_remove(name),
+        };
+    }
+    Ok(command)
+}
+
+/// Runs every `[[processes]]` entry side by side and waits for all of them.
+/// Returns the result of the first one to fail, or else of the last one to
+/// exit.
+fn run_processes(config: &Config) -> anyhow::Result<Option<ExitStatus>> {
+    if config
+        .process_configs
+        .iter()
+        .any(|process| process.command.as_path().as_os_str().is_empty())
+    {
+        return Err(Coded::new(
+            ErrorCode::CommandNotSpecified,
+            "command not specified for one of [[processes]]",
+        )
+        .into());
+    }
+    let mut pipes = connect_processes(&config.process_configs)?;
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        for (index, process) in config.process_configs.iter().enumerate() {
+            let delay = process
+                .start_delay
+                .or(process.stagger.filter(|_| index > 0));
+            if let Some(delay) = delay {
+                verbose::log(format_args!(
+                    "waiting {} before starting {}",
+                    humantime::format_duration(delay),
+                    process.command.as_path().display()
+                ));
+                std::thread::sleep(delay);
+            }
+            let sender = sender.clone();
+            let pipes = std::mem::take(&mut pipes[index]);
+            scope.spawn(move || {
+                let result = run_once(process, !process.detach, &pipes);
+                // Let the other end of the pipes see the command is done.
+                drop(pipes);
+                let failed = match &result {
+                    Ok(status) => status.is_some_and(|status| !succeeded(process, status)),
+                    Err(_) => true,
+                };
+                let _ = sender.send((result, failed));
+            });
+        }
+    });
+    drop(sender);
+    let mut last = Ok(None);
+    for (result, failed) in receiver {
+        if failed {
+            return result;
+        }
+        last = result;
+    }
+    last
+}
+
+/// Creates the pipes `pipe_to` asks for, by entry.
+fn connect_processes(processes: &[Config]) -> anyhow::Result<Vec<PipeEnds>> {
+    let mut pipes: Vec<PipeEnds> = processes.iter().map(|_| PipeEnds::default()).collect();
+    for (index, process) in processes.iter().enumerate() {
+        let Some(target) = &process.pipe_to else {
+            continue;
+        };
+        let consumer = processes
+            .iter()
+            .enumerate()
+            .position(|(other, consumer)| other != index && consumer.name.as_ref() == Some(target))
+            .ok_or_else(|| {
+                Coded::new(
+                    ErrorCode::ConfigInvalid,
+                    format!(
+                        "pipe_to names {}, which no other [[processes]] entry is called",
+                        target
+                    ),
+                )
+            })?;
+        if process.stdout.is_some() {
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("an entry piping to {} can't also set stdout", target),
+            )
+            .into());
+        }
+        if processes[consumer].stdin.is_some() || pipes[consumer].stdin.is_some() {
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("{} gets its stdin from elsewhere already", target),
+            )
+            .into());
+        }
+        let (reader, writer) = io::pipe().context("create pipe")?;
+        pipes[index].stdout = Some(writer);
+        pipes[consumer].stdin = Some(reader);
+    }
+    Ok(pipes)
+}
+
+/// Runs a `[[pre]]` or `[[post]]` command and waits for it to succeed.
+fn run_hook(config: &Config, hook: &HookConfig, exit_code: Option<i32>) -> anyhow::Result<()> {
+    let mut command = command_in_environment(config, &hook.command, &hook.args)?;
+    if let Some(cwd) = &hook.cwd {
+        command.current_dir(config_relative(config, cwd));
+    }
+    if let Some(exit_code) = exit_code {
+        command.env("LAUNCHER_EXIT_CODE", exit_code.to_string());
+    }
+    command.envs(&hook.env);
+    let status = command
+        .status()
+        .map_err(|error| spawn_error(error, &hook.command))?;
+    if !status.success() {
+        anyhow::bail!(
+            "hook {} exited with {}",
+            hook.command.display(),
+            exit_status::describe(status)
+        );
+    }
+    Ok(())
+}
+
+/// Runs the `[check]` command with the environment and working directory the
+/// main command would get.
+fn run_check(config: &Config) -> anyhow::Result<()> {
+    let Some(check) = &config.check else {
+        return Err(Coded::new(ErrorCode::ConfigInvalid, "no [check] section in config").into());
+    };
+    let pattern = match &check.output {
+        Some(pattern) => Some(regex::Regex::new(pattern).map_err(|error| {
+            anyhow::Error::new(error).context(Coded::new(
+                ErrorCode::ConfigInvalid,
+                "parse check output pattern",
+            ))
+        })?),
+        None => None,
+    };
+    let output = command_in_environment(config, &check.command, &check.args)?
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|error| spawn_error(error, &check.command))?;
+    let code = exit_status::code(output.status);
+    if code != check.exit_code {
+        anyhow::bail!(
+            "check {} exited with code {}, expected {}",
+            check.command.display(),
+            code,
+            check.exit_code
+        );
+    }
+    if let Some(pattern) = pattern {
+        let text =
+            String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+        if !pattern.is_match(&text) {
+            anyhow::bail!(
+                "output of check {} does not match {}",
+                check.command.display(),
+                pattern
+            );
+        }
+    }
+    eprintln!("check {} passed", check.command.display());
+    Ok(())
+}
+
+/// Starts a packaged app, which gets its environment from the system rather
+/// than from the launcher.
+fn run_app(config: &Config, aumid: &str, wait: bool) -> anyhow::Result<Option<ExitStatus>> {
+    if !config.env.is_empty() {
+        eprintln!("warning: [env] is not passed to packaged app {}", aumid);
+    }
+    let pid = package::activate(aumid, &config.args)
+        .map_err(|error| error.context(Coded::new(ErrorCode::SpawnFailed, "spawn packaged app")))?;
+    trace::spawn(Path::new(aumid), pid);
+    if !wait {
+        return Ok(None);
+    }
+    let code = processes::wait_for_exit(pid)?;
+    trace::exit(pid, code as i32);
+    #[cfg(unix)]
+    let code = (code as i32) << 8;
+    Ok(Some(ExitStatus::from_raw(code)))
+}
+
+fn run_as_user(
+    config: &Config,
+    run_as: &run_as::RunAs,
+    wait: bool,
+) -> anyhow::Result<Option<ExitStatus>> {
+    let command = build_command(config)?;
+    let environment = resolved_environment(config, &command);
+    let child = run_as
+        .spawn(&command, &environment)
+        .map_err(|error| error.context(Coded::new(ErrorCode::SpawnFailed, "spawn command")))?;
+    trace::spawn(config.command.as_path(), child.id());
+    if !wait {
+        return Ok(None);
+    }
+    let code = child.wait()?;
+    trace::exit(child.id(), code as i32);
+    #[cfg(unix)]
+    let code = (code as i32) << 8;
+    Ok(Some(ExitStatus::from_raw(code)))
+}
+
+/// Starts the command the way the config describes, running hooks, waiting
+/// for it and restarting it as configured. Returns the exit status of the
+/// last run when it was waited for.
+fn launch(config: &Config, monitor: bool) -> anyhow::Result<Option<ExitStatus>> {
+    if config.elevate.is_some_and(elevate::Elevate::always) && !elevate::is_elevated() {
+        // The elevated launcher reads the same config and does the rest.
+        return Err(elevate::Relaunched(elevate::relaunch_self(true)?).into());
+    }
+
+    if let Some(skip) = &config.skip_if_running {
+        if already_running(skip)? {
+            eprintln!("{} is already running", config.command.as_path().display());
+            return Ok(None);
+        }
+    }
+
+    let (lock_name, on_running) = match &config.single_instance {
+        Some(SingleInstanceConfig::Enabled(true)) => (Some(None), OnRunning::Exit),
+        Some(SingleInstanceConfig::Detailed { name, on_running }) => {
+            (Some(name.as_deref()), *on_running)
+        }
+        _ => (None, OnRunning::Exit),
+    };
+    let _instance_lock = match lock_name {
+        Some(name) => {
+            let key = config.path.as_deref().unwrap_or(config.command.as_path());
+            match instances::InstanceLock::acquire(name, key, on_running == OnRunning::Wait)? {
+                Some(lock) => Some(lock),
+                None => {
+                    eprintln!("{} is already running", config.command.as_path().display());
+                    if on_running == OnRunning::Focus {
+                        focus_running(config)?;
+                    }
+                    return Ok(None);
+                }
+            }
+        }
+        None => None,
+    };
+
+    if config.detach && config.exit_code_file.is_some() && !monitor {
+        spawn_monitor()?;
+        return Ok(None);
+    }
+    let wait = !config.detach || monitor;
+
+    if let Some(delay) = config.delay {
+        wait_before_spawn(
+            delay,
+            config.delay_message.as_deref(),
+            config.delay_countdown,
+        );
+    }
+
+    for hook in &config.pre {
+        run_hook(config, hook, None)?;
+    }
+
+    if let Some(wait_for) = &config.wait_for {
+        let file = wait_for
+            .file
+            .as_ref()
+            .map(|file| config_relative(config, file));
+        wait_for.wait(file.as_deref())?;
+    }
+
+    let mut runs = 0;
+    let mut restarts = 0;
+    let status = if !config.process_configs.is_empty() {
+        run_processes(config)?
+    } else {
+        loop {
+            let started = Instant::now();
+            let status = run_once(config, wait, &PipeEnds::default())?;
+            if let (Some(path), Some(status)) = (&config.exit_code_file, status) {
+                write_exit_code(&config_relative(config, path), status)?;
+            }
+            if let (Some(restart), Some(status)) = (&config.restart, status) {
+                let wanted = restart.on == RestartOn::Always || !succeeded(config, status);
+                if wanted
+                    && restart
+                        .max_retries
+                        .is_none_or(|max_retries| restarts < max_retries)
+                {
+                    let delay = restart.delay.saturating_mul(1 << restarts.min(16));
+                    let delay = restart
+                        .max_delay
+                        .map_or(delay, |max_delay| delay.min(max_delay));
+                    eprintln!(
+                        "{} exited with {}, restarting in {}",
+                        config.command.as_path().display(),
+                        exit_status::describe(status),
+                        humantime::format_duration(delay)
+                    );
+                    trace::restart(config.command.as_path(), "exited");
+                    std::thread::sleep(delay);
+                    restarts += 1;
+                    continue;
+                }
+            }
+            runs += 1;
+            let Some(loop_mode) = &config.loop_mode else {
+                break status;
+            };
+            if loop_mode.max_runs != 0 && runs >= loop_mode.max_runs {
+                break status;
+            }
+            if let Some(status) = status.filter(|status| !succeeded(config, *status)) {
+                eprintln!(
+                    "{} exited with {}",
+                    config.command.as_path().display(),
+                    exit_status::describe(status)
+                );
+            }
+            let interval = loop_mode.every + random_duration(loop_mode.jitter);
+            std::thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+    };
+
+    if let Some(status) = status {
+        for hook in &config.post {
+            if let Err(error) = run_hook(config, hook, Some(exit_status::code(status))) {
+                eprintln!("warning: {:?}", error);
+            }
+        }
+    }
+
+    Ok(status)
+}
+
+/// Re-launches the launcher as a detached process that waits for the child,
+/// so its exit code can still be recorded once this process is gone.
+fn spawn_monitor() -> anyhow::Result<()> {
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    let mut command = Command::new(launcher_path);
+    command
+        .arg("--monitor")
+        .args(std::env::args_os().skip(1))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(windows)]
+    command.creation_flags(DETACHED_PROCESS);
+    #[cfg(unix)]
+    detach(&mut command);
+    command.spawn().context("spawn monitor process")?;
+    Ok(())
+}
+
+fn write_exit_code(path: &Path, status: ExitStatus) -> anyhow::Result<()> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("create directory {}", parent.display()))?;
+    }
+    let content = format!(
+        "{}\n{}\n",
+        exit_status::code(status),
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    );
+    std::fs::write(path, content)
+        .with_context(|| format!("write exit code file {}", path.display()))
+}
+
+#[cfg(windows)]
+fn default_shell() -> PathBuf {
+    std::env::var_os("COMSPEC")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("cmd.exe"))
+}
+
+#[cfg(unix)]
+fn default_shell() -> PathBuf {
+    std::env::var_os("SHELL")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/bin/sh"))
+}
+
+/// The full environment the command starts with, sorted by name.
+fn resolved_environment(config: &Config, command: &Command) -> Vec<(OsString, OsString)> {
+    let mut environment: Vec<(OsString, OsString)> = if replaces_environment(config) {
+        Vec::new()
+    } else {
+        std::env::vars_os().collect()
+    };
+    for (name, value) in command.get_envs() {
+        environment.retain(|(existing, _)| !same_env_name(existing, name));
+        if let Some(value) = value {
+            environment.push((name.to_os_string(), value.to_os_string()));
+        }
+    }
+    environment.sort();
+    environment
+}
@@ -0,0 +1,104 @@
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// File a stream of the command is written to. Relative paths are resolved
+/// against the config file.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum LogFile {
+    Path(PathBuf),
+    Detailed {
+        path: PathBuf,
+        /// Start the file over on every launch instead of appending to it.
+        #[serde(default)]
+        truncate: bool,
+        /// Size in bytes above which the file is rotated before a launch.
+        max_size: Option<u64>,
+        /// Number of rotated files kept as `<path>.1`, `<path>.2`, ...
+        #[serde(default = "default_keep")]
+        keep: u32,
+    },
+}
+
+/// Stream name that discards the stream instead of naming a file.
+pub const NULL: &str = "null";
+/// `stderr` name that sends it wherever stdout goes.
+pub const STDOUT: &str = "stdout";
+
+fn default_keep() -> u32 {
+    1
+}
+
+impl LogFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            LogFile::Path(path) | LogFile::Detailed { path, .. } => path,
+        }
+    }
+
+    /// Whether the path is the special name `name` rather than a file.
+    pub fn is(&self, name: &str) -> bool {
+        self.path() == Path::new(name)
+    }
+
+    /// Opens the log file at `path`, rotating it first if it grew too big.
+    pub fn open(&self, path: &Path) -> anyhow::Result<File> {
+        let (truncate, max_size, keep) = match self {
+            LogFile::Path(_) => (false, None, 0),
+            LogFile::Detailed {
+                truncate,
+                max_size,
+                keep,
+                ..
+            } => (*truncate, *max_size, *keep),
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create directory {}", parent.display()))?;
+        }
+        if let Some(max_size) = max_size {
+            let size = std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+            if size > max_size {
+                rotate(path, keep)?;
+            }
+        }
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(!truncate)
+            .truncate(truncate)
+            .open(path)
+            .with_context(|| format!("open log file {}", path.display()))
+    }
+}
+
+fn rotated(path: &Path, index: u32) -> PathBuf {
+    let mut rotated = path.as_os_str().to_os_string();
+    rotated.push(format!(".{}", index));
+    PathBuf::from(rotated)
+}
+
+/// Shifts `<path>.1` .. `<path>.<keep - 1>` up by one and moves `path` to
+/// `<path>.1`, dropping the oldest file.
+fn rotate(path: &Path, keep: u32) -> anyhow::Result<()> {
+    if keep == 0 {
+        return std::fs::remove_file(path)
+            .with_context(|| format!("remove log file {}", path.display()));
+    }
+    let _ = std::fs::remove_file(rotated(path, keep));
+    for index in (1..keep).rev() {
+        let from = rotated(path, index);
+        if from.exists() {
+            std::fs::rename(&from, rotated(path, index + 1))
+                .with_context(|| format!("rotate log file {}", from.display()))?;
+        }
+    }
+    std::fs::rename(path, rotated(path, 1))
+        .with_context(|| format!("rotate log file {}", path.display()))
+}
@@ -0,0 +1,186 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::process::Child;
+use std::thread::{self, JoinHandle};
+#[cfg(windows)]
+use windows::core::HSTRING;
+#[cfg(windows)]
+use windows::Win32::Globalization::{MultiByteToWideChar, CP_ACP, CP_OEMCP, MB_PRECOMPOSED};
+#[cfg(windows)]
+use windows::Win32::System::Diagnostics::Debug::OutputDebugStringW;
+
+/// Encoding of the child's output, converted to UTF-8 before it is forwarded.
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Oem,
+    Ansi,
+    Utf8,
+    Utf16,
+}
+
+impl Encoding {
+    fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            #[cfg(windows)]
+            Encoding::Oem => decode_code_page(CP_OEMCP, bytes),
+            #[cfg(windows)]
+            Encoding::Ansi => decode_code_page(CP_ACP, bytes),
+            // Unix has no legacy code pages.
+            #[cfg(not(windows))]
+            Encoding::Oem | Encoding::Ansi => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16 => {
+                let units: Vec<u16> = bytes
+                    .chunks(2)
+                    .map(|unit| u16::from_le_bytes([unit[0], *unit.get(1).unwrap_or(&0)]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+fn decode_code_page(code_page: u32, bytes: &[u8]) -> String {
+    let len = unsafe { MultiByteToWideChar(code_page, MB_PRECOMPOSED, bytes, None) };
+    let mut wide = vec![0u16; len.max(0) as usize];
+    let len = unsafe { MultiByteToWideChar(code_page, MB_PRECOMPOSED, bytes, Some(&mut wide)) };
+    wide.truncate(len.max(0) as usize);
+    String::from_utf16_lossy(&wide)
+}
+
+/// Where a forwarded stream ends up.
+pub enum Sink {
+    Stdout,
+    Stderr,
+    File(File),
+    Pipe(io::PipeWriter),
+    Null,
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Sink::Stdout => io::stdout().write(buf),
+            Sink::Stderr => io::stderr().write(buf),
+            Sink::File(file) => file.write(buf),
+            Sink::Pipe(pipe) => pipe.write(buf),
+            Sink::Null => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Sink::Stdout => io::stdout().flush(),
+            Sink::Stderr => io::stderr().flush(),
+            Sink::File(file) => file.flush(),
+            Sink::Pipe(pipe) => pipe.flush(),
+            Sink::Null => Ok(()),
+        }
+    }
+}
+
+/// Forwards the child's piped stdout and stderr line by line to `sinks`,
+/// and to `OutputDebugStringW` if `debug_output` is set.
+pub fn forward(
+    child: &mut Child,
+    sinks: [Sink; 2],
+    encoding: Option<Encoding>,
+    debug_output: bool,
+) -> Vec<JoinHandle<()>> {
+    let [stdout_sink, stderr_sink] = sinks;
+    let mut forwarders = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        forwarders.push(spawn_forwarder(stdout, stdout_sink, encoding, debug_output));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        forwarders.push(spawn_forwarder(stderr, stderr_sink, encoding, debug_output));
+    }
+    forwarders
+}
+
+fn spawn_forwarder<R>(
+    reader: R,
+    mut sink: Sink,
+    encoding: Option<Encoding>,
+    debug_output: bool,
+) -> JoinHandle<()>
+where
+    R: Read + Send + 'static,
+{
+    thread::spawn(move || {
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        while let Ok(len) = reader.read_until(b'\n', &mut line) {
+            if len == 0 {
+                break;
+            }
+            // A UTF-16 newline is followed by a zero byte.
+            if matches!(encoding, Some(Encoding::Utf16)) && line.len() % 2 == 1 {
+                let mut byte = [0u8];
+                if let Ok(1) = reader.read(&mut byte) {
+                    line.push(byte[0]);
+                }
+            }
+            let text = match encoding {
+                Some(encoding) => {
+                    let text = encoding.decode(&line);
+                    let _ = sink.write_all(text.as_bytes());
+                    text
+                }
+                None => {
+                    let _ = sink.write_all(&line);
+                    String::from_utf8_lossy(&line).into_owned()
+                }
+            };
+            if debug_output {
+                debug_output_string(&text);
+            }
+            line.clear();
+        }
+    })
+}
+
+#[cfg(windows)]
+fn debug_output_string(text: &str) {
+    unsafe { OutputDebugStringW(&HSTRING::from(text)) };
+}
+
+#[cfg(not(windows))]
+fn debug_output_string(_text: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::Encoding;
+
+    #[test]
+    fn decodes_utf16() {
+        let bytes: Vec<u8> = "grüße\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!(Encoding::Utf16.decode(&bytes), "grüße\r\n");
+    }
+
+    #[test]
+    fn decodes_oem() {
+        // Every OEM code page agrees with ASCII.
+        assert_eq!(
+            Encoding::Oem.decode(b"C:\\Temp> dir\r\n"),
+            "C:\\Temp> dir\r\n"
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn decodes_oem_beyond_ascii() {
+        use windows::Win32::Globalization::GetOEMCP;
+        // Code page 437 and 850, the usual OEM code pages, both have ü at 0x81.
+        if matches!(unsafe { GetOEMCP() }, 437 | 850) {
+            assert_eq!(Encoding::Oem.decode(b"gr\x81n"), "grün");
+        }
+    }
+}
@@ -0,0 +1,40 @@
+#[cfg(windows)]
+use crate::cmdline;
+#[cfg(windows)]
+use anyhow::Context;
+#[cfg(windows)]
+use windows::core::HSTRING;
+#[cfg(windows)]
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CLSCTX_LOCAL_SERVER, COINIT_APARTMENTTHREADED,
+};
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{
+    ApplicationActivationManager, IApplicationActivationManager, AO_NONE,
+};
+
+/// Starts the packaged app `aumid` through the activation manager and returns
+/// the id of the created process.
+#[cfg(windows)]
+pub fn activate(aumid: &str, args: &[String]) -> anyhow::Result<u32> {
+    unsafe {
+        CoInitializeEx(None, COINIT_APARTMENTTHREADED)
+            .ok()
+            .context("initialize COM")?;
+        let manager: IApplicationActivationManager =
+            CoCreateInstance(&ApplicationActivationManager, None, CLSCTX_LOCAL_SERVER)
+                .context("create application activation manager")?;
+        manager
+            .ActivateApplication(
+                &HSTRING::from(aumid),
+                &HSTRING::from(cmdline::join(args)),
+                AO_NONE,
+            )
+            .with_context(|| format!("activate packaged app {}", aumid))
+    }
+}
+
+#[cfg(not(windows))]
+pub fn activate(_aumid: &str, _args: &[String]) -> anyhow::Result<u32> {
+    anyhow::bail!("packaged apps are only supported on Windows")
+}
@@ -0,0 +1,106 @@
+use crate::{same_env_name, LAUNCH_ID_VAR};
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+/// File the child's process id is written to once it started. Relative paths
+/// are resolved against the config file.
+#[derive(Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum PidFile {
+    Path(PathBuf),
+    Detailed {
+        path: PathBuf,
+        /// Write a JSON record with the start time, command and a hash of the
+        /// environment instead of the bare id.
+        #[serde(default)]
+        json: bool,
+    },
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    pid: u32,
+    launch_id: &'a str,
+    start: String,
+    command: String,
+    args: Vec<String>,
+    /// FNV-1a hash of the sorted `NAME=VALUE` pairs the command started with,
+    /// to tell whether two runs got the same environment. `LAUNCH_ID` is left
+    /// out since it differs on every run.
+    env_hash: String,
+}
+
+impl PidFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            PidFile::Path(path) | PidFile::Detailed { path, .. } => path,
+        }
+    }
+
+    /// Writes the file at `path` for the child `pid` started as `command`
+    /// with `environment`, sorted by name.
+    pub fn write(
+        &self,
+        path: &Path,
+        pid: u32,
+        launch_id: &str,
+        command: &Command,
+        environment: &[(OsString, OsString)],
+    ) -> anyhow::Result<()> {
+        let content = match self {
+            PidFile::Detailed { json: true, .. } => {
+                let env_hash = environment
+                    .iter()
+                    .filter(|(name, _)| !same_env_name(name, LAUNCH_ID_VAR.as_ref()))
+                    .flat_map(|(name, value)| {
+                        [
+                            name.as_encoded_bytes(),
+                            b"=",
+                            value.as_encoded_bytes(),
+                            b"\0",
+                        ]
+                    })
+                    .flatten()
+                    .fold(0xcbf29ce484222325u64, |hash, byte| {
+                        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+                    });
+                let record = Record {
+                    pid,
+                    launch_id,
+                    start: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+                    command: command.get_program().to_string_lossy().into_owned(),
+                    args: command
+                        .get_args()
+                        .map(|arg| arg.to_string_lossy().into_owned())
+                        .collect(),
+                    env_hash: format!("{:016x}", env_hash),
+                };
+                serde_json::to_string_pretty(&record).context("serialize pid record")? + "\n"
+            }
+            _ => format!("{}\n", pid),
+        };
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create directory {}", parent.display()))?;
+        }
+        std::fs::write(path, content).with_context(|| format!("write pid file {}", path.display()))
+    }
+}
+
+/// Removes the pid file when dropped, once the launcher stopped waiting for
+/// the child.
+pub struct RemoveOnDrop(pub PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
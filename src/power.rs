@@ -0,0 +1,44 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+#[cfg(windows)]
+use windows::Win32::System::Power::{
+    SetThreadExecutionState, ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED,
+};
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum KeepAwake {
+    /// Keep the system from sleeping.
+    System,
+    /// Keep the system from sleeping and the display on.
+    Display,
+}
+
+/// Keeps the system awake until dropped. The request belongs to the thread
+/// that created the guard.
+pub struct KeepAwakeGuard;
+
+impl KeepAwakeGuard {
+    #[cfg(windows)]
+    pub fn set(mode: KeepAwake) -> Self {
+        let flags = match mode {
+            KeepAwake::System => ES_CONTINUOUS | ES_SYSTEM_REQUIRED,
+            KeepAwake::Display => ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED,
+        };
+        unsafe { SetThreadExecutionState(flags) };
+        KeepAwakeGuard
+    }
+
+    #[cfg(not(windows))]
+    pub fn set(_mode: KeepAwake) -> Self {
+        eprintln!("warning: keep_awake is only supported on Windows");
+        KeepAwakeGuard
+    }
+}
+
+#[cfg(windows)]
+impl Drop for KeepAwakeGuard {
+    fn drop(&mut self) {
+        unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+    }
+}
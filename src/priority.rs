@@ -0,0 +1,94 @@
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+#[cfg(windows)]
+use std::os::windows::io::AsRawHandle;
+use std::process::Child;
+#[cfg(windows)]
+use windows::Win32::Foundation::HANDLE;
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    SetPriorityClass, SetProcessAffinityMask, ABOVE_NORMAL_PRIORITY_CLASS,
+    BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS, IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS,
+    REALTIME_PRIORITY_CLASS,
+};
+
+/// Scheduling priority of the child: a Windows priority class, or the
+/// matching nice value on Unix.
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Idle,
+    BelowNormal,
+    Normal,
+    AboveNormal,
+    High,
+    Realtime,
+}
+
+#[cfg(windows)]
+pub fn set_priority(child: &Child, priority: Priority) -> anyhow::Result<()> {
+    let class = match priority {
+        Priority::Idle => IDLE_PRIORITY_CLASS,
+        Priority::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+        Priority::Normal => NORMAL_PRIORITY_CLASS,
+        Priority::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+        Priority::High => HIGH_PRIORITY_CLASS,
+        Priority::Realtime => REALTIME_PRIORITY_CLASS,
+    };
+    unsafe { SetPriorityClass(HANDLE(child.as_raw_handle()), class) }.context("set priority class")
+}
+
+#[cfg(unix)]
+pub fn set_priority(child: &Child, priority: Priority) -> anyhow::Result<()> {
+    let nice = match priority {
+        Priority::Idle => 19,
+        Priority::BelowNormal => 10,
+        Priority::Normal => 0,
+        Priority::AboveNormal => -5,
+        Priority::High => -10,
+        Priority::Realtime => -20,
+    };
+    // Raising the priority above normal needs privileges.
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, child.id() as libc::id_t, nice) } == -1 {
+        return Err(std::io::Error::last_os_error()).context("set nice value");
+    }
+    Ok(())
+}
+
+/// Restricts the child to the CPUs numbered in `cpus`.
+#[cfg(windows)]
+pub fn set_affinity(child: &Child, cpus: &[u32]) -> anyhow::Result<()> {
+    let mut mask = 0usize;
+    for &cpu in cpus {
+        if cpu >= usize::BITS {
+            anyhow::bail!("cpu {} is out of range for affinity", cpu);
+        }
+        mask |= 1 << cpu;
+    }
+    unsafe { SetProcessAffinityMask(HANDLE(child.as_raw_handle()), mask) }
+        .context("set affinity mask")
+}
+
+#[cfg(target_os = "linux")]
+pub fn set_affinity(child: &Child, cpus: &[u32]) -> anyhow::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for &cpu in cpus {
+            if cpu as usize >= libc::CPU_SETSIZE as usize {
+                anyhow::bail!("cpu {} is out of range for affinity", cpu);
+            }
+            libc::CPU_SET(cpu as usize, &mut set);
+        }
+        let pid = child.id() as libc::pid_t;
+        if libc::sched_setaffinity(pid, size_of::<libc::cpu_set_t>(), &set) == -1 {
+            return Err(std::io::Error::last_os_error()).context("set affinity");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub fn set_affinity(_child: &Child, _cpus: &[u32]) -> anyhow::Result<()> {
+    anyhow::bail!("affinity is not supported on this platform")
+}
@@ -0,0 +1,255 @@
+use anyhow::Context;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+use std::process::Child;
+use std::thread;
+use std::time::Duration;
+
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub exe_name: String,
+}
+
+/// Lists the processes currently running on the machine, with the file name
+/// of their executable.
+pub fn list() -> anyhow::Result<Vec<ProcessEntry>> {
+    Ok(pids()?
+        .into_iter()
+        // The process may exit while we look at it.
+        .filter_map(|pid| {
+            Some(ProcessEntry {
+                pid,
+                exe_name: exe_name(pid)?,
+            })
+        })
+        .collect())
+}
+
+#[cfg(target_os = "linux")]
+fn pids() -> anyhow::Result<Vec<u32>> {
+    Ok(std::fs::read_dir("/proc")
+        .context("list processes")?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect())
+}
+
+/// The executable's name from the `exe` link, or from the command line when
+/// the link can't be read, as for processes of other users. `comm` is cut
+/// to 15 characters, so it is only used for kernel threads, which have
+/// neither.
+#[cfg(target_os = "linux")]
+fn exe_name(pid: u32) -> Option<String> {
+    let dir = Path::new("/proc").join(pid.to_string());
+    if let Ok(exe) = std::fs::read_link(dir.join("exe")) {
+        return file_name(exe.as_os_str().as_bytes());
+    }
+    let cmdline = std::fs::read(dir.join("cmdline")).ok()?;
+    match cmdline
+        .split(|&c| c == 0)
+        .next()
+        .filter(|arg0| !arg0.is_empty())
+    {
+        Some(arg0) => file_name(arg0),
+        None => Some(
+            std::fs::read_to_string(dir.join("comm"))
+                .ok()?
+                .trim_end()
+                .to_string(),
+        ),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn pids() -> anyhow::Result<Vec<u32>> {
+    let count = unsafe { libc::proc_listallpids(std::ptr::null_mut(), 0) };
+    if count < 0 {
+        return Err(std::io::Error::last_os_error()).context("list processes");
+    }
+    // Room for processes started in between.
+    let mut pids: Vec<libc::pid_t> = vec![0; count as usize + 64];
+    let count = unsafe {
+        libc::proc_listallpids(
+            pids.as_mut_ptr().cast(),
+            (pids.len() * size_of::<libc::pid_t>()) as libc::c_int,
+        )
+    };
+    if count < 0 {
+        return Err(std::io::Error::last_os_error()).context("list processes");
+    }
+    pids.truncate(count as usize);
+    Ok(pids
+        .into_iter()
+        .filter(|&pid| pid > 0)
+        .map(|pid| pid as u32)
+        .collect())
+}
+
+#[cfg(target_os = "macos")]
+fn exe_name(pid: u32) -> Option<String> {
+    let mut path = vec![0u8; libc::PROC_PIDPATHINFO_MAXSIZE as usize];
+    let len = unsafe {
+        libc::proc_pidpath(
+            pid as libc::c_int,
+            path.as_mut_ptr().cast(),
+            path.len() as u32,
+        )
+    };
+    if len <= 0 {
+        return None;
+    }
+    file_name(&path[..len as usize])
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn pids() -> anyhow::Result<Vec<u32>> {
+    anyhow::bail!("listing processes is only supported on Windows, Linux and macOS")
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn exe_name(_pid: u32) -> Option<String> {
+    None
+}
+
+fn file_name(path: &[u8]) -> Option<String> {
+    Path::new(OsStr::from_bytes(path))
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Returns the id of a running process whose executable is named `exe_name`.
+pub fn find_by_name(exe_name: &str) -> anyhow::Result<Option<u32>> {
+    Ok(list()?
+        .into_iter()
+        .find(|process| process.exe_name == exe_name)
+        .map(|process| process.pid))
+}
+
+pub fn parent_pid() -> anyhow::Result<u32> {
+    Ok(std::os::unix::process::parent_id())
+}
+
+fn is_running(pid: u32) -> bool {
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Asks the process `pid` to exit with SIGTERM, whatever its process group.
+pub fn terminate(pid: u32, _own_group: bool) -> anyhow::Result<()> {
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } == -1 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("send SIGTERM to process {}", pid));
+    }
+    Ok(())
+}
+
+pub fn enable_efficiency_mode(_child: &Child) -> anyhow::Result<()> {
+    anyhow::bail!("efficiency_mode is only supported on Windows")
+}
+
+/// Terminates the process `child` once the process `followed` exits. The
+/// watcher thread stops on its own when `child` exits first.
+pub fn kill_when_exits(followed: u32, child: u32) -> anyhow::Result<()> {
+    if !is_running(followed) {
+        anyhow::bail!("followed process {} is not running", followed);
+    }
+    thread::spawn(move || {
+        while is_running(child) {
+            if !is_running(followed) {
+                unsafe { libc::kill(child as libc::pid_t, libc::SIGTERM) };
+                return;
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+    });
+    Ok(())
+}
+
+pub fn wait_for_exit(_pid: u32) -> anyhow::Result<u32> {
+    anyhow::bail!("waiting for processes other than children is only supported on Windows")
+}
+
+/// Reads the environment of the running process `pid` from /proc.
+#[cfg(target_os = "linux")]
+pub fn environment_of(pid: u32) -> anyhow::Result<Vec<(OsString, OsString)>> {
+    let block = std::fs::read(format!("/proc/{}/environ", pid))
+        .with_context(|| format!("read environment of process {}", pid))?;
+    Ok(parse_environment(block.split(|&c| c == 0)))
+}
+
+/// Reads the environment of the running process `pid` from the arguments
+/// block `KERN_PROCARGS2` returns: the argument count, the executable path
+/// padded with zeros, the arguments and then the environment, each ending
+/// with a zero.
+#[cfg(target_os = "macos")]
+pub fn environment_of(pid: u32) -> anyhow::Result<Vec<(OsString, OsString)>> {
+    let failed = || {
+        anyhow::Error::new(std::io::Error::last_os_error())
+            .context(format!("read environment of process {}", pid))
+    };
+    let mut arg_max: libc::c_int = 0;
+    let mut size = size_of::<libc::c_int>();
+    let mut mib = [libc::CTL_KERN, libc::KERN_ARGMAX];
+    if unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            2,
+            (&mut arg_max as *mut libc::c_int).cast(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    } == -1
+    {
+        return Err(failed());
+    }
+    let mut block = vec![0u8; arg_max as usize];
+    let mut size = block.len();
+    let mut mib = [libc::CTL_KERN, libc::KERN_PROCARGS2, pid as libc::c_int];
+    if unsafe {
+        libc::sysctl(
+            mib.as_mut_ptr(),
+            3,
+            block.as_mut_ptr().cast(),
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    } == -1
+    {
+        return Err(failed());
+    }
+    block.truncate(size);
+    let Some((argc, rest)) = block.split_first_chunk::<4>() else {
+        return Ok(Vec::new());
+    };
+    let argc = i32::from_ne_bytes(*argc).max(0) as usize;
+    let path_end = rest.iter().position(|&c| c == 0).unwrap_or(rest.len());
+    let strings_start = rest[path_end..]
+        .iter()
+        .position(|&c| c != 0)
+        .map_or(rest.len(), |padding| path_end + padding);
+    let mut strings = rest[strings_start..].split(|&c| c == 0).skip(argc);
+    Ok(parse_environment(&mut strings))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn environment_of(_pid: u32) -> anyhow::Result<Vec<(OsString, OsString)>> {
+    anyhow::bail!(
+        "reading the environment of other processes is only supported on Windows, Linux and macOS"
+    )
+}
+
+/// Splits `NAME=value` entries, up to the first empty one.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn parse_environment<'a>(entries: impl Iterator<Item = &'a [u8]>) -> Vec<(OsString, OsString)> {
+    entries
+        .take_while(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let separator = entry.iter().position(|&c| c == b'=')?;
+            Some((
+                OsString::from_vec(entry[..separator].to_vec()),
+                OsString::from_vec(entry[separator + 1..].to_vec()),
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+use crate::window;
+use anyhow::Context;
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use std::os::windows::io::AsRawHandle;
+use std::process::Child;
+use std::thread;
+use windows::Wdk::System::Threading::{NtQueryInformationProcess, ProcessBasicInformation};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0};
+use windows::Win32::System::Console::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT};
+use windows::Win32::System::Diagnostics::Debug::ReadProcessMemory;
+use windows::Win32::System::Diagnostics::ToolHelp::{
+    CreateToolhelp32Snapshot, Process32FirstW, Process32NextW, PROCESSENTRY32W, TH32CS_SNAPPROCESS,
+};
+use windows::Win32::System::Threading::{
+    GetExitCodeProcess, OpenProcess, ProcessPowerThrottling, SetProcessInformation,
+    TerminateProcess, WaitForMultipleObjects, WaitForSingleObject, INFINITE,
+    PROCESS_BASIC_INFORMATION, PROCESS_POWER_THROTTLING_CURRENT_VERSION,
+    PROCESS_POWER_THROTTLING_EXECUTION_SPEED, PROCESS_POWER_THROTTLING_STATE,
+    PROCESS_QUERY_INFORMATION, PROCESS_QUERY_LIMITED_INFORMATION, PROCESS_SYNCHRONIZE,
+    PROCESS_TERMINATE, PROCESS_VM_READ,
+};
+
+// Offsets of PEB.ProcessParameters and of RTL_USER_PROCESS_PARAMETERS.Environment
+// and .EnvironmentSize, which the SDK headers don't expose.
+#[cfg(target_pointer_width = "64")]
+const PROCESS_PARAMETERS_OFFSET: usize = 0x20;
+#[cfg(target_pointer_width = "64")]
+const ENVIRONMENT_OFFSET: usize = 0x80;
+#[cfg(target_pointer_width = "64")]
+const ENVIRONMENT_SIZE_OFFSET: usize = 0x3f0;
+#[cfg(target_pointer_width = "32")]
+const PROCESS_PARAMETERS_OFFSET: usize = 0x10;
+#[cfg(target_pointer_width = "32")]
+const ENVIRONMENT_OFFSET: usize = 0x48;
+#[cfg(target_pointer_width = "32")]
+const ENVIRONMENT_SIZE_OFFSET: usize = 0x290;
+
+pub struct ProcessEntry {
+    pub pid: u32,
+    pub parent_pid: u32,
+    pub exe_name: String,
+}
+
+/// Lists the processes currently running on the machine.
+pub fn list() -> anyhow::Result<Vec<ProcessEntry>> {
+    let mut processes = Vec::new();
+    unsafe {
+        let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPPROCESS, 0).context("list processes")?;
+        let mut entry = PROCESSENTRY32W {
+            dwSize: size_of::<PROCESSENTRY32W>() as u32,
+            ..Default::default()
+        };
+        let mut next = Process32FirstW(snapshot, &mut entry);
+        while next.is_ok() {
+            let len = entry
+                .szExeFile
+                .iter()
+                .position(|&c| c == 0)
+                .unwrap_or(entry.szExeFile.len());
+            processes.push(ProcessEntry {
+                pid: entry.th32ProcessID,
+                parent_pid: entry.th32ParentProcessID,
+                exe_name: String::from_utf16_lossy(&entry.szExeFile[..len]),
+            });
+            next = Process32NextW(snapshot, &mut entry);
+        }
+        let _ = CloseHandle(snapshot);
+    }
+    Ok(processes)
+}
+
+/// Returns the id of a running process whose executable is named `exe_name`.
+pub fn find_by_name(exe_name: &str) -> anyhow::Result<Option<u32>> {
+    Ok(list()?
+        .into_iter()
+        .find(|process| process.exe_name.eq_ignore_ascii_case(exe_name))
+        .map(|process| process.pid))
+}
+
+pub fn parent_pid() -> anyhow::Result<u32> {
+    let pid = std::process::id();
+    list()?
+        .into_iter()
+        .find(|process| process.pid == pid)
+        .map(|process| process.parent_pid)
+        .context("find launcher process")
+}
+
+/// Waits for the process `pid`, which isn't a child of the launcher, and
+/// returns its exit code.
+pub fn wait_for_exit(pid: u32) -> anyhow::Result<u32> {
+    unsafe {
+        let process = OpenProcess(
+            PROCESS_SYNCHRONIZE | PROCESS_QUERY_LIMITED_INFORMATION,
+            false,
+            pid,
+        )
+        .with_context(|| format!("open process {}", pid))?;
+        WaitForSingleObject(process, INFINITE);
+        let mut code = 0;
+        let result = GetExitCodeProcess(process, &mut code);
+        let _ = CloseHandle(process);
+        result.with_context(|| format!("get exit code of process {}", pid))?;
+        Ok(code)
+    }
+}
+
+/// Asks the process `pid` to exit: GUI apps get their window closed, console
+/// programs get Ctrl+Break if `own_group` says they lead their own process
+/// group. Anything else is terminated right away, as Ctrl+Break would go to
+/// every process on the console otherwise.
+pub fn terminate(pid: u32, own_group: bool) -> anyhow::Result<()> {
+    if let Some(hwnd) = window::find_by_pid(pid) {
+        if window::close(hwnd) {
+            return Ok(());
+        }
+    }
+    if own_group {
+        return unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) }
+            .with_context(|| format!("send Ctrl+Break to process {}", pid));
+    }
+    unsafe {
+        let process = OpenProcess(PROCESS_TERMINATE, false, pid)
+            .with_context(|| format!("open process {}", pid))?;
+        let result = TerminateProcess(process, 1);
+        let _ = CloseHandle(process);
+        result.with_context(|| format!("terminate process {}", pid))
+    }
+}
+
+/// Turns on EcoQoS power throttling for `child`, as Task Manager's efficiency
+/// mode does.
+pub fn enable_efficiency_mode(child: &Child) -> anyhow::Result<()> {
+    let state = PROCESS_POWER_THROTTLING_STATE {
+        Version: PROCESS_POWER_THROTTLING_CURRENT_VERSION,
+        ControlMask: PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
+        StateMask: PROCESS_POWER_THROTTLING_EXECUTION_SPEED,
+    };
+    unsafe {
+        SetProcessInformation(
+            HANDLE(child.as_raw_handle()),
+            ProcessPowerThrottling,
+            &state as *const _ as *const _,
+            size_of::<PROCESS_POWER_THROTTLING_STATE>() as u32,
+        )
+    }
+    .context("enable power throttling")
+}
+
+/// Terminates the process `child` once the process `followed` exits. The
+/// watcher thread stops on its own when `child` exits first.
+pub fn kill_when_exits(followed: u32, child: u32) -> anyhow::Result<()> {
+    let (followed, child) = unsafe {
+        let followed = OpenProcess(PROCESS_SYNCHRONIZE, false, followed)
+            .with_context(|| format!("open followed process {}", followed))?;
+        let child = match OpenProcess(PROCESS_SYNCHRONIZE | PROCESS_TERMINATE, false, child) {
+            Ok(child) => child,
+            Err(error) => {
+                let _ = CloseHandle(followed);
+                return Err(anyhow::Error::new(error).context("open child process"));
+            }
+        };
+        (followed.0 as usize, child.0 as usize)
+    };
+    thread::spawn(move || unsafe {
+        let followed = HANDLE(followed as _);
+        let child = HANDLE(child as _);
+        if WaitForMultipleObjects(&[followed, child], false, INFINITE) == WAIT_OBJECT_0 {
+            let _ = TerminateProcess(child, 1);
+        }
+        let _ = CloseHandle(followed);
+        let _ = CloseHandle(child);
+    });
+    Ok(())
+}
+
+/// Reads the environment block of the running process `pid`. The process has
+/// to have the same bitness as the launcher.
+pub fn environment_of(pid: u32) -> anyhow::Result<Vec<(OsString, OsString)>> {
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_INFORMATION | PROCESS_VM_READ, false, pid)
+            .with_context(|| format!("open process {}", pid))?;
+        let block = read_environment_block(process);
+        let _ = CloseHandle(process);
+        let block = block.with_context(|| format!("read environment of process {}", pid))?;
+        Ok(parse_environment_block(&block))
+    }
+}
+
+/// The `NAME=VALUE` entries of an environment block.
+pub fn parse_environment_block(block: &[u16]) -> Vec<(OsString, OsString)> {
+    block
+        .split(|&c| c == 0)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            // Skip the per-drive "=C:=C:\dir" entries.
+            let separator = entry.iter().skip(1).position(|&c| c == '=' as u16)? + 1;
+            Some((
+                OsString::from_wide(&entry[..separator]),
+                OsString::from_wide(&entry[separator + 1..]),
+            ))
+        })
+        .collect()
+}
+
+unsafe fn read_environment_block(process: HANDLE) -> anyhow::Result<Vec<u16>> {
+    let mut info: PROCESS_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+    let mut len = 0;
+    unsafe {
+        NtQueryInformationProcess(
+            process,
+            ProcessBasicInformation,
+            &mut info as *mut _ as *mut _,
+            size_of::<PROCESS_BASIC_INFORMATION>() as u32,
+            &mut len,
+        )
+    }
+    .ok()
+    .context("query process information")?;
+    let parameters: usize = unsafe {
+        read_memory(
+            process,
+            info.PebBaseAddress as usize + PROCESS_PARAMETERS_OFFSET,
+        )?
+    };
+    let environment: usize = unsafe { read_memory(process, parameters + ENVIRONMENT_OFFSET)? };
+    let size: usize = unsafe { read_memory(process, parameters + ENVIRONMENT_SIZE_OFFSET)? };
+    let mut block = vec![0u16; size / 2];
+    unsafe {
+        ReadProcessMemory(
+            process,
+            environment as *const _,
+            block.as_mut_ptr() as *mut _,
+            block.len() * 2,
+            None,
+        )
+    }
+    .context("read environment block")?;
+    Ok(block)
+}
+
+unsafe fn read_memory<T: Default>(process: HANDLE, address: usize) -> anyhow::Result<T> {
+    let mut value = T::default();
+    unsafe {
+        ReadProcessMemory(
+            process,
+            address as *const _,
+            &mut value as *mut T as *mut _,
+            size_of::<T>(),
+            None,
+        )
+    }
+    .context("read process memory")?;
+    Ok(value)
+}
@@ -0,0 +1,124 @@
+use crate::error::{Coded, ErrorCode};
+use toml::Value;
+
+/// Merges `[profiles.<name>]` over the top level of the config document,
+/// after the profiles it `extends`, nearest last. Tables are merged key by
+/// key, any other value replaces the default.
+pub fn apply(document: &mut Value, name: &str) -> anyhow::Result<()> {
+    let mut chain: Vec<(String, Value)> = Vec::new();
+    let mut next = Some(name.to_string());
+    while let Some(name) = next {
+        if chain.iter().any(|(seen, _)| *seen == name) {
+            let cycle: Vec<&str> = chain.iter().map(|(seen, _)| seen.as_str()).collect();
+            return Err(Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!(
+                    "profile {} extends itself: {} -> {}",
+                    name,
+                    cycle.join(" -> "),
+                    name
+                ),
+            )
+            .into());
+        }
+        let mut profile = document
+            .get("profiles")
+            .and_then(|profiles| profiles.get(&name))
+            .cloned()
+            .ok_or_else(|| {
+                Coded::new(
+                    ErrorCode::ConfigInvalid,
+                    format!("no profile named {}", name),
+                )
+            })?;
+        next = match profile
+            .as_table_mut()
+            .and_then(|table| table.remove("extends"))
+        {
+            Some(Value::String(parent)) => Some(parent),
+            Some(_) => {
+                return Err(Coded::new(
+                    ErrorCode::ConfigInvalid,
+                    format!("extends of profile {} is not a profile name", name),
+                )
+                .into())
+            }
+            None => None,
+        };
+        chain.push((name, profile));
+    }
+    for (_, profile) in chain.into_iter().rev() {
+        merge(document, profile);
+    }
+    Ok(())
+}
+
+/// Merges `overlay` over `base`, table by table.
+pub fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Table(base), Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::apply;
+    use toml::Value;
+
+    fn document(source: &str) -> Value {
+        toml::from_str(source).unwrap()
+    }
+
+    #[test]
+    fn applies_extended_profiles_first() {
+        let mut config = document(
+            r#"
+            command = "app"
+            [env]
+            MODE = "dev"
+            [profiles.staging]
+            env = { MODE = "staging", API = "staging.example" }
+            cwd = "staging"
+            [profiles.prod]
+            extends = "staging"
+            env = { MODE = "prod" }
+            "#,
+        );
+        apply(&mut config, "prod").unwrap();
+        assert_eq!(config["env"]["MODE"].as_str(), Some("prod"));
+        assert_eq!(config["env"]["API"].as_str(), Some("staging.example"));
+        assert_eq!(config["cwd"].as_str(), Some("staging"));
+        assert!(config.get("extends").is_none());
+    }
+
+    #[test]
+    fn rejects_cycles() {
+        let mut config = document(
+            r#"
+            [profiles.a]
+            extends = "b"
+            [profiles.b]
+            extends = "a"
+            "#,
+        );
+        let error = apply(&mut config, "a").unwrap_err();
+        assert_eq!(error.to_string(), "profile a extends itself: a -> b -> a");
+    }
+
+    #[test]
+    fn rejects_unknown_parents() {
+        let mut config = document("[profiles.a]\nextends = \"missing\"\n");
+        let error = apply(&mut config, "a").unwrap_err();
+        assert_eq!(error.to_string(), "no profile named missing");
+    }
+}
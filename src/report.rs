@@ -0,0 +1,76 @@
+use crate::{exit_status, verbose};
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+use std::time::SystemTime;
+
+/// What a single run of the command was started with and how it ended.
+#[derive(Serialize)]
+pub struct Report {
+    launch_id: String,
+    command: String,
+    args: Vec<String>,
+    cwd: Option<String>,
+    /// Variables set (or removed, as `null`) on top of the launcher's own
+    /// environment, with values from secret sources as `***`.
+    env: BTreeMap<String, Option<String>>,
+    pid: u32,
+    start: String,
+    end: Option<String>,
+    exit_code: Option<i32>,
+    #[serde(skip)]
+    started: SystemTime,
+}
+
+impl Report {
+    pub fn new(command: &Command, pid: u32, launch_id: &str) -> Self {
+        let started = SystemTime::now();
+        let text = |value: &OsStr| verbose::mask(&value.to_string_lossy(), "***");
+        Report {
+            launch_id: launch_id.to_string(),
+            command: text(command.get_program()),
+            args: command.get_args().map(text).collect(),
+            cwd: command.get_current_dir().map(|cwd| text(cwd.as_os_str())),
+            env: command
+                .get_envs()
+                .map(|(name, value)| (name.to_string_lossy().into_owned(), value.map(text)))
+                .collect(),
+            pid,
+            start: humantime::format_rfc3339_seconds(started).to_string(),
+            end: None,
+            exit_code: None,
+            started,
+        }
+    }
+
+    /// Records the end of the run; `status` is `None` when the watchdog killed it.
+    pub fn finish(&mut self, status: Option<ExitStatus>) {
+        self.end = Some(humantime::format_rfc3339_seconds(SystemTime::now()).to_string());
+        self.exit_code = status.map(exit_status::code);
+    }
+
+    /// Writes the report to `path`, where `{timestamp}` stands for the start
+    /// time of the run and `{launch_id}` for the launch id.
+    pub fn write(&self, path: &str) -> anyhow::Result<()> {
+        let timestamp = humantime::format_rfc3339_seconds(self.started)
+            .to_string()
+            .replace(['-', ':'], "");
+        let path = PathBuf::from(
+            path.replace("{timestamp}", &timestamp)
+                .replace("{launch_id}", &self.launch_id),
+        );
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("create directory {}", parent.display()))?;
+        }
+        let content = serde_json::to_string_pretty(self).context("serialize launch report")?;
+        std::fs::write(&path, content)
+            .with_context(|| format!("write launch report {}", path.display()))
+    }
+}
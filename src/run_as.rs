@@ -0,0 +1,260 @@
+use crate::same_env_name;
+#[cfg(windows)]
+use crate::{credential, processes, script};
+#[cfg(windows)]
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::ffi::OsString;
+#[cfg(windows)]
+use std::os::windows::ffi::OsStrExt;
+#[cfg(windows)]
+use std::process::Command;
+#[cfg(windows)]
+use windows::core::{HSTRING, PCWSTR, PWSTR};
+#[cfg(windows)]
+use windows::Win32::Foundation::{CloseHandle, HANDLE};
+#[cfg(windows)]
+use windows::Win32::Security::{LogonUserW, LOGON32_LOGON_INTERACTIVE, LOGON32_PROVIDER_DEFAULT};
+#[cfg(windows)]
+use windows::Win32::System::Environment::{CreateEnvironmentBlock, DestroyEnvironmentBlock};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{
+    CreateProcessWithLogonW, GetExitCodeProcess, WaitForSingleObject, CREATE_UNICODE_ENVIRONMENT,
+    INFINITE, LOGON_WITH_PROFILE, PROCESS_INFORMATION, STARTUPINFOW,
+};
+
+/// Account the command is started under.
+#[derive(Deserialize, JsonSchema)]
+#[cfg_attr(not(windows), allow(dead_code))]
+pub struct RunAs {
+    username: String,
+    /// `.` for a local account if not set.
+    domain: Option<String>,
+    /// Name of the credential holding the account's password.
+    credential: String,
+}
+
+/// Process started under another account.
+#[cfg(windows)]
+pub struct LogonChild {
+    pid: u32,
+    process: HANDLE,
+}
+
+#[cfg(windows)]
+impl RunAs {
+    /// Starts `command` in the account's own environment, loading the
+    /// account's profile first, with the changes `environment` makes to the
+    /// launcher's environment applied to it.
+    pub fn spawn(
+        &self,
+        command: &Command,
+        environment: &[(OsString, OsString)],
+    ) -> anyhow::Result<LogonChild> {
+        let password = credential::read(&self.credential)?;
+        let username = HSTRING::from(self.username.as_str());
+        let domain = HSTRING::from(self.domain.as_deref().unwrap_or("."));
+        let password = HSTRING::from(password);
+        let own: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+        let environment = with_changes(
+            self.environment(&username, &domain, &password)?,
+            &own,
+            environment,
+        );
+        let mut command_line = script::command_line(command);
+        command_line.push(0);
+        let mut block: Vec<u16> = Vec::new();
+        for (name, value) in &environment {
+            block.extend(name.encode_wide());
+            block.push('=' as u16);
+            block.extend(value.encode_wide());
+            block.push(0);
+        }
+        block.extend([0, 0]);
+        let current_dir = command
+            .get_current_dir()
+            .map(|dir| HSTRING::from(dir.as_os_str()));
+        let startup_info = STARTUPINFOW {
+            cb: size_of::<STARTUPINFOW>() as u32,
+            ..Default::default()
+        };
+        let mut info = PROCESS_INFORMATION::default();
+        unsafe {
+            CreateProcessWithLogonW(
+                &username,
+                &domain,
+                &password,
+                LOGON_WITH_PROFILE,
+                PCWSTR::null(),
+                Some(PWSTR(command_line.as_mut_ptr())),
+                CREATE_UNICODE_ENVIRONMENT,
+                Some(block.as_ptr() as *const _),
+                current_dir
+                    .as_ref()
+                    .map_or(PCWSTR::null(), |dir| PCWSTR(dir.as_ptr())),
+                &startup_info,
+                &mut info,
+            )
+            .with_context(|| format!("start command as {}", self.username))?;
+            let _ = CloseHandle(info.hThread);
+        }
+        Ok(LogonChild {
+            pid: info.dwProcessId,
+            process: info.hProcess,
+        })
+    }
+
+    /// The environment a process of the account starts with.
+    fn environment(
+        &self,
+        username: &HSTRING,
+        domain: &HSTRING,
+        password: &HSTRING,
+    ) -> anyhow::Result<Vec<(OsString, OsString)>> {
+        unsafe {
+            let mut token = HANDLE::default();
+            LogonUserW(
+                username,
+                domain,
+                password,
+                LOGON32_LOGON_INTERACTIVE,
+                LOGON32_PROVIDER_DEFAULT,
+                &mut token,
+            )
+            .with_context(|| format!("log on as {}", self.username))?;
+            let mut block = std::ptr::null_mut();
+            let created = CreateEnvironmentBlock(&mut block, Some(token), false);
+            let _ = CloseHandle(token);
+            created.with_context(|| format!("create environment of {}", self.username))?;
+            // The block ends with an empty entry.
+            let start = block as *const u16;
+            let mut len = 0;
+            while *start.add(len) != 0 || *start.add(len + 1) != 0 {
+                len += 1;
+            }
+            let environment =
+                processes::parse_environment_block(std::slice::from_raw_parts(start, len + 1));
+            let _ = DestroyEnvironmentBlock(block);
+            Ok(environment)
+        }
+    }
+}
+
+#[cfg(windows)]
+impl LogonChild {
+    pub fn id(&self) -> u32 {
+        self.pid
+    }
+
+    /// Waits for the process and returns its exit code.
+    pub fn wait(&self) -> anyhow::Result<u32> {
+        let mut code = 0;
+        unsafe {
+            WaitForSingleObject(self.process, INFINITE);
+            GetExitCodeProcess(self.process, &mut code)
+                .with_context(|| format!("get exit code of process {}", self.pid))?;
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for LogonChild {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.process) };
+    }
+}
+
+/// `base` with the changes `resolved` makes to `own`: variables it sets to
+/// another value are set, variables it doesn't have are removed.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn with_changes(
+    mut base: Vec<(OsString, OsString)>,
+    own: &[(OsString, OsString)],
+    resolved: &[(OsString, OsString)],
+) -> Vec<(OsString, OsString)> {
+    let find = |environment: &[(OsString, OsString)], name: &OsString| {
+        environment
+            .iter()
+            .find(|(existing, _)| same_env_name(existing, name))
+            .map(|(_, value)| value.clone())
+    };
+    for (name, _) in own {
+        if find(resolved, name).is_none() {
+            base.retain(|(existing, _)| !same_env_name(existing, name));
+        }
+    }
+    for (name, value) in resolved {
+        if find(own, name).as_ref() != Some(value) {
+            base.retain(|(existing, _)| !same_env_name(existing, name));
+            base.push((name.clone(), value.clone()));
+        }
+    }
+    base.sort();
+    base
+}
+
+#[cfg(not(windows))]
+pub struct LogonChild;
+
+#[cfg(not(windows))]
+impl RunAs {
+    pub fn spawn(
+        &self,
+        _command: &std::process::Command,
+        _environment: &[(std::ffi::OsString, std::ffi::OsString)],
+    ) -> anyhow::Result<LogonChild> {
+        anyhow::bail!("run_as is only supported on Windows")
+    }
+}
+
+#[cfg(not(windows))]
+impl LogonChild {
+    pub fn id(&self) -> u32 {
+        0
+    }
+
+    pub fn wait(&self) -> anyhow::Result<u32> {
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::with_changes;
+    use std::ffi::OsString;
+
+    fn environment(entries: &[(&str, &str)]) -> Vec<(OsString, OsString)> {
+        entries
+            .iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_the_accounts_own_variables() {
+        let base = environment(&[
+            ("TEMP", "C:\\Users\\other\\Temp"),
+            ("USERPROFILE", "C:\\Users\\other"),
+        ]);
+        let own = environment(&[
+            ("OLD", "1"),
+            ("TEMP", "C:\\Users\\me\\Temp"),
+            ("USERPROFILE", "C:\\Users\\me"),
+        ]);
+        let resolved = environment(&[
+            ("MODE", "test"),
+            ("TEMP", "D:\\scratch"),
+            ("USERPROFILE", "C:\\Users\\me"),
+        ]);
+        assert_eq!(
+            with_changes(base, &own, &resolved),
+            environment(&[
+                ("MODE", "test"),
+                ("TEMP", "D:\\scratch"),
+                ("USERPROFILE", "C:\\Users\\other"),
+            ])
+        );
+    }
+}
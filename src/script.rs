@@ -0,0 +1,235 @@
+use crate::cmdline;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::ffi::OsStr;
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+#[cfg(windows)]
+use windows::core::{HSTRING, PWSTR};
+#[cfg(windows)]
+use windows::Win32::System::Environment::ExpandEnvironmentStringsW;
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{AssocQueryStringW, ASSOCF_NONE, ASSOCSTR_COMMAND};
+
+#[cfg(windows)]
+const POWERSHELL: &str = "powershell.exe";
+#[cfg(not(windows))]
+const POWERSHELL: &str = "pwsh";
+/// Switches cmd.exe is started with for a snippet, followed by the snippet's
+/// line as a raw argument.
+const CMD_SWITCHES: [&str; 3] = ["/d", "/s", "/c"];
+
+/// Shell running the configured command as a snippet; `true` picks cmd.exe on
+/// Windows and /bin/sh elsewhere.
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(untagged)]
+pub enum ShellConfig {
+    Default(bool),
+    Named(Shell),
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    Cmd,
+    Powershell,
+    Sh,
+}
+
+impl ShellConfig {
+    pub fn shell(self) -> Option<Shell> {
+        match self {
+            ShellConfig::Default(false) => None,
+            ShellConfig::Default(true) if cfg!(windows) => Some(Shell::Cmd),
+            ShellConfig::Default(true) => Some(Shell::Sh),
+            ShellConfig::Named(shell) => Some(shell),
+        }
+    }
+}
+
+/// Builds the command running `snippet` in `shell`, with `args` appended to
+/// it quoted for that shell.
+pub fn shell_command(shell: Shell, snippet: &str, args: &[String]) -> Command {
+    match shell {
+        Shell::Cmd => {
+            let line = std::iter::once(snippet.to_string())
+                .chain(args.iter().map(|arg| cmdline::quote_always(arg)))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut command = Command::new("cmd.exe");
+            command.args(CMD_SWITCHES);
+            // cmd.exe takes the rest of its command line as is, so std's
+            // escaping must not be applied to it.
+            #[cfg(windows)]
+            command.raw_arg(format!("\"{}\"", line));
+            #[cfg(not(windows))]
+            command.arg(line);
+            command
+        }
+        Shell::Powershell => {
+            let script = std::iter::once(snippet.to_string())
+                .chain(
+                    args.iter()
+                        .map(|arg| format!("'{}'", arg.replace('\'', "''"))),
+                )
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut command = Command::new(POWERSHELL);
+            command.args(["-NoProfile", "-Command", &script]);
+            command
+        }
+        Shell::Sh => {
+            let script = std::iter::once(snippet.to_string())
+                .chain(
+                    args.iter()
+                        .map(|arg| format!("'{}'", arg.replace('\'', "'\\''"))),
+                )
+                .collect::<Vec<_>>()
+                .join(" ");
+            let mut command = Command::new("/bin/sh");
+            command.args(["-c", &script]);
+            command
+        }
+    }
+}
+
+/// The command line `command` is started with, for starting it other than
+/// through `std::process`. The line [`shell_command`] gives cmd.exe is passed
+/// on as it is, as `raw_arg` does.
+#[cfg(windows)]
+pub fn command_line(command: &Command) -> Vec<u16> {
+    let args: Vec<&OsStr> = command.get_args().collect();
+    let raw = match args.split_last() {
+        Some((line, switches))
+            if command.get_program() == "cmd.exe" && *switches == CMD_SWITCHES.map(OsStr::new) =>
+        {
+            Some(*line)
+        }
+        _ => None,
+    };
+    let quoted = if raw.is_some() {
+        &args[..args.len() - 1]
+    } else {
+        &args[..]
+    };
+    let mut line =
+        cmdline::join_wide(std::iter::once(command.get_program()).chain(quoted.iter().copied()));
+    if let Some(raw) = raw {
+        use std::os::windows::ffi::OsStrExt;
+        line.push(' ' as u16);
+        line.extend(raw.encode_wide());
+    }
+    line
+}
+
+/// Builds the command running `program` with `args`, going through the
+/// interpreter registered for scripts that aren't executables themselves.
+/// Unix scripts name their interpreter in their shebang line instead.
+pub fn command(program: &Path, args: &[String]) -> Command {
+    let extension = program
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_ascii_lowercase);
+    let mut command = match extension.as_deref() {
+        // std runs batch files through cmd.exe with the right escaping.
+        None | Some("exe" | "com" | "bat" | "cmd") => Command::new(program),
+        Some("ps1") => {
+            let mut command = Command::new(POWERSHELL);
+            command
+                .args(["-NoProfile", "-ExecutionPolicy", "Bypass", "-File"])
+                .arg(program);
+            command
+        }
+        Some(extension) => match open_command(extension) {
+            Some(template) => return command_from_template(&template, program, args),
+            None => Command::new(program),
+        },
+    };
+    command.args(args);
+    command
+}
+
+/// Looks up the shell `open` command line registered for `.extension`.
+#[cfg(windows)]
+fn open_command(extension: &str) -> Option<String> {
+    let extension = HSTRING::from(format!(".{}", extension));
+    let verb = HSTRING::from("open");
+    let mut len = 0u32;
+    unsafe {
+        AssocQueryStringW(
+            ASSOCF_NONE,
+            ASSOCSTR_COMMAND,
+            &extension,
+            &verb,
+            None,
+            &mut len,
+        )
+        .ok()
+        .ok()?;
+        let mut buffer = vec![0u16; len as usize];
+        AssocQueryStringW(
+            ASSOCF_NONE,
+            ASSOCSTR_COMMAND,
+            &extension,
+            &verb,
+            Some(PWSTR(buffer.as_mut_ptr())),
+            &mut len,
+        )
+        .ok()
+        .ok()?;
+        let template = HSTRING::from_wide(&buffer[..len.saturating_sub(1) as usize]);
+        let expanded_len = ExpandEnvironmentStringsW(&template, None);
+        let mut expanded = vec![0u16; expanded_len as usize];
+        let expanded_len = ExpandEnvironmentStringsW(&template, Some(&mut expanded));
+        if expanded_len == 0 {
+            return Some(template.to_string_lossy());
+        }
+        Some(String::from_utf16_lossy(
+            &expanded[..expanded_len.saturating_sub(1) as usize],
+        ))
+    }
+}
+
+#[cfg(not(windows))]
+fn open_command(_extension: &str) -> Option<String> {
+    None
+}
+
+/// Fills an association template like `"C:\py.exe" "%1" %*` with the script
+/// path and arguments.
+fn command_from_template(template: &str, script: &Path, args: &[String]) -> Command {
+    let mut tokens = cmdline::split(template).into_iter();
+    let mut command = Command::new(tokens.next().unwrap_or_default());
+    let mut script_added = false;
+    let mut args_added = false;
+    for token in tokens {
+        match token.as_str() {
+            "%1" | "%L" | "%l" => {
+                command.arg(script);
+                script_added = true;
+            }
+            "%*" => {
+                if !script_added {
+                    command.arg(script);
+                    script_added = true;
+                }
+                command.args(args);
+                args_added = true;
+            }
+            // Remaining placeholders (%2, %I, ...) have nothing to refer to.
+            token if token.starts_with('%') && token.len() == 2 => {}
+            _ => {
+                command.arg(token);
+            }
+        }
+    }
+    if !script_added {
+        command.arg(script);
+    }
+    if !args_added {
+        command.args(args);
+    }
+    command
+}
@@ -0,0 +1,148 @@
+#[cfg(windows)]
+use crate::cmdline;
+#[cfg(windows)]
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
+#[cfg(windows)]
+use toml_edit::{value, Array};
+#[cfg(windows)]
+use windows::core::{Interface, HSTRING};
+#[cfg(windows)]
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, IPersistFile, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    STGM_READ,
+};
+#[cfg(windows)]
+use windows::Win32::UI::Shell::{IShellLinkW, ShellLink};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{SW_SHOWMAXIMIZED, SW_SHOWMINNOACTIVE};
+
+#[cfg(windows)]
+// Shell link strings are limited to INFOTIPSIZE characters.
+const BUFFER_LEN: usize = 1024;
+
+#[cfg(windows)]
+fn create_shell_link() -> anyhow::Result<IShellLinkW> {
+    unsafe {
+        CoInitializeEx(None, COINIT_APARTMENTTHREADED)
+            .ok()
+            .context("initialize COM")?;
+        CoCreateInstance(&ShellLink, None, CLSCTX_INPROC_SERVER).context("create shell link")
+    }
+}
+
+#[cfg(windows)]
+fn from_wide(buffer: &[u16]) -> String {
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    String::from_utf16_lossy(&buffer[..len])
+}
+
+#[cfg(windows)]
+/// Converts a `.lnk` shortcut into an equivalent launcher config.
+pub fn import(shortcut_path: &Path) -> anyhow::Result<DocumentMut> {
+    let link = create_shell_link()?;
+    let mut buffer = [0u16; BUFFER_LEN];
+    let mut document = DocumentMut::new();
+    unsafe {
+        link.cast::<IPersistFile>()?
+            .Load(&HSTRING::from(shortcut_path), STGM_READ)
+            .with_context(|| format!("load shortcut {}", shortcut_path.display()))?;
+
+        link.GetPath(&mut buffer, std::ptr::null_mut(), 0)
+            .context("get shortcut target")?;
+        let target = from_wide(&buffer);
+        if target.is_empty() {
+            anyhow::bail!(
+                "shortcut {} does not point to a file",
+                shortcut_path.display()
+            );
+        }
+        document["command"] = value(target);
+
+        link.GetArguments(&mut buffer)
+            .context("get shortcut arguments")?;
+        let args: Array = cmdline::split(&from_wide(&buffer)).into_iter().collect();
+        if !args.is_empty() {
+            document["args"] = value(args);
+        }
+
+        link.GetWorkingDirectory(&mut buffer)
+            .context("get shortcut working directory")?;
+        let cwd = from_wide(&buffer);
+        if !cwd.is_empty() {
+            document["cwd"] = value(cwd);
+        }
+
+        let window = match link.GetShowCmd().context("get shortcut window state")? {
+            SW_SHOWMAXIMIZED => Some("maximized"),
+            SW_SHOWMINNOACTIVE => Some("minimized"),
+            _ => None,
+        };
+        if let Some(window) = window {
+            document.decor_mut().set_prefix(format!(
+                "# The shortcut started the window {}, which the launcher does not apply.\n",
+                window
+            ));
+        }
+    }
+    Ok(document)
+}
+
+#[cfg(windows)]
+/// Writes a `.lnk` shortcut that runs the launcher with `launcher_args`.
+///
+/// `out` is either the shortcut file itself or the directory to put
+/// `<name>.lnk` into.
+pub fn export(
+    out: &Path,
+    name: &str,
+    launcher_args: &[String],
+    icon: Option<&Path>,
+    cwd: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    let shortcut_path = if out
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("lnk"))
+    {
+        out.to_path_buf()
+    } else {
+        out.join(name).with_extension("lnk")
+    };
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    let link = create_shell_link()?;
+    unsafe {
+        link.SetPath(&HSTRING::from(launcher_path.as_path()))
+            .context("set shortcut target")?;
+        link.SetArguments(&HSTRING::from(cmdline::join(launcher_args)))
+            .context("set shortcut arguments")?;
+        if let Some(icon) = icon {
+            link.SetIconLocation(&HSTRING::from(icon), 0)
+                .context("set shortcut icon")?;
+        }
+        if let Some(cwd) = cwd {
+            link.SetWorkingDirectory(&HSTRING::from(cwd))
+                .context("set shortcut working directory")?;
+        }
+        link.cast::<IPersistFile>()?
+            .Save(&HSTRING::from(shortcut_path.as_path()), true)
+            .with_context(|| format!("save shortcut {}", shortcut_path.display()))?;
+    }
+    Ok(shortcut_path)
+}
+
+#[cfg(unix)]
+pub fn import(_shortcut_path: &Path) -> anyhow::Result<DocumentMut> {
+    anyhow::bail!("shortcuts are only supported on Windows")
+}
+
+#[cfg(unix)]
+pub fn export(
+    _out: &Path,
+    _name: &str,
+    _launcher_args: &[String],
+    _icon: Option<&Path>,
+    _cwd: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    anyhow::bail!("shortcuts are only supported on Windows")
+}
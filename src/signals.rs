@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Once;
+#[cfg(windows)]
+use windows::core::BOOL;
+#[cfg(windows)]
+use windows::Win32::Foundation::CloseHandle;
+#[cfg(windows)]
+use windows::Win32::System::Console::{
+    GenerateConsoleCtrlEvent, SetConsoleCtrlHandler, CTRL_BREAK_EVENT, CTRL_C_EVENT,
+};
+#[cfg(windows)]
+use windows::Win32::System::Threading::{OpenProcess, TerminateProcess, PROCESS_TERMINATE};
+
+/// Number of children signals can be forwarded to at the same time.
+const SLOTS: usize = 64;
+
+// Read by the handler, which can't borrow anything from the launcher. A pid of
+// 0 marks a free slot.
+static CHILD_PIDS: [AtomicU32; SLOTS] = [const { AtomicU32::new(0) }; SLOTS];
+static OWN_GROUP: [AtomicBool; SLOTS] = [const { AtomicBool::new(false) }; SLOTS];
+static KILL_ON_TERMINATE: [AtomicBool; SLOTS] = [const { AtomicBool::new(false) }; SLOTS];
+static INSTALL: Once = Once::new();
+
+/// Keeps the launcher alive on Ctrl+C and termination requests while the
+/// child runs and passes them on to it, until dropped.
+///
+/// A child sharing the launcher's console or terminal already gets Ctrl+C
+/// itself, so it is only forwarded to a child in its own process group.
+pub struct Forwarder(Option<usize>);
+
+impl Forwarder {
+    pub fn install(pid: u32, own_group: bool, kill_on_terminate: bool) -> Self {
+        INSTALL.call_once(install_handler);
+        for slot in 0..SLOTS {
+            if CHILD_PIDS[slot]
+                .compare_exchange(0, pid, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+            {
+                OWN_GROUP[slot].store(own_group, Ordering::SeqCst);
+                KILL_ON_TERMINATE[slot].store(kill_on_terminate, Ordering::SeqCst);
+                return Forwarder(Some(slot));
+            }
+        }
+        eprintln!("warning: too many children to forward signals to");
+        Forwarder(None)
+    }
+}
+
+impl Drop for Forwarder {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0 {
+            CHILD_PIDS[slot].store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Calls `forward` with the pid, own group and kill on terminate settings of
+/// every child, returning whether there was any.
+fn for_each_child(mut forward: impl FnMut(u32, bool, bool)) -> bool {
+    let mut any = false;
+    for slot in 0..SLOTS {
+        let pid = CHILD_PIDS[slot].load(Ordering::SeqCst);
+        if pid != 0 {
+            forward(
+                pid,
+                OWN_GROUP[slot].load(Ordering::SeqCst),
+                KILL_ON_TERMINATE[slot].load(Ordering::SeqCst),
+            );
+            any = true;
+        }
+    }
+    any
+}
+
+#[cfg(windows)]
+fn install_handler() {
+    if let Err(error) = unsafe { SetConsoleCtrlHandler(Some(handler), true) } {
+        eprintln!("warning: install console control handler: {}", error);
+    }
+}
+
+#[cfg(windows)]
+unsafe extern "system" fn handler(event: u32) -> BOOL {
+    match event {
+        CTRL_C_EVENT | CTRL_BREAK_EVENT => for_each_child(|pid, own_group, _| {
+            // Ctrl+C can't be sent to another process group, Ctrl+Break can.
+            if own_group {
+                let _ = unsafe { GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid) };
+            }
+        })
+        .into(),
+        // The console is closing or the user logs off: the launcher is
+        // terminated once this returns.
+        _ => {
+            for_each_child(|pid, _, kill_on_terminate| {
+                if kill_on_terminate {
+                    if let Ok(process) = unsafe { OpenProcess(PROCESS_TERMINATE, false, pid) } {
+                        let _ = unsafe { TerminateProcess(process, 1) };
+                        let _ = unsafe { CloseHandle(process) };
+                    }
+                }
+            });
+            false.into()
+        }
+    }
+}
+
+#[cfg(unix)]
+fn install_handler() {
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe { libc::signal(signal, handler as *const () as libc::sighandler_t) };
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn handler(signal: libc::c_int) {
+    let any = for_each_child(|pid, own_group, kill_on_terminate| {
+        let pid = pid as libc::pid_t;
+        unsafe {
+            if signal == libc::SIGINT {
+                if own_group {
+                    libc::kill(pid, signal);
+                }
+            } else if kill_on_terminate {
+                libc::kill(pid, libc::SIGKILL);
+            } else {
+                libc::kill(pid, signal);
+            }
+        }
+    });
+    if !any {
+        // No child to pass it on to, so act on it as if never handled.
+        unsafe {
+            libc::signal(signal, libc::SIG_DFL);
+            libc::raise(signal);
+        }
+    }
+}
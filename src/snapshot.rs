@@ -0,0 +1,177 @@
+use crate::error::{Coded, ErrorCode};
+use crate::{same_env_name, verbose, LAUNCH_ID_PLACEHOLDER, LAUNCH_ID_VAR};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Command;
+
+/// Stands for values from secret sources, which a snapshot must not contain.
+const SECRET_PLACEHOLDER: &str = "{secret}";
+
+/// The resolved command line and environment changes of a config, as stored
+/// for `launcher check --against`.
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    command: String,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cwd: Option<String>,
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    /// Snapshot of `command`, with `launch_id` put back as its `{launch_id}`
+    /// placeholder so a snapshot matches every launch of the same config.
+    /// Values from secret sources are stored and compared as `{secret}`.
+    pub fn new(command: &Command, launch_id: &str) -> Self {
+        let text = |value: &OsStr| {
+            verbose::mask(&value.to_string_lossy(), SECRET_PLACEHOLDER)
+                .replace(launch_id, LAUNCH_ID_PLACEHOLDER)
+        };
+        Snapshot {
+            command: text(command.get_program()),
+            args: command.get_args().map(text).collect(),
+            cwd: command.get_current_dir().map(|cwd| text(cwd.as_os_str())),
+            env: command
+                .get_envs()
+                .filter(|(name, _)| !same_env_name(name, LAUNCH_ID_VAR.as_ref()))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string_lossy().into_owned(), text(value?)))
+                })
+                .collect(),
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read(path)
+            .with_context(|| format!("read snapshot file {}", path.display()))?;
+        toml::from_slice(&content).with_context(|| {
+            Coded::new(
+                ErrorCode::ConfigInvalid,
+                format!("parse snapshot file {}", path.display()),
+            )
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let content = toml::to_string(self).context("serialize snapshot")?;
+        std::fs::write(path, content)
+            .with_context(|| format!("write snapshot file {}", path.display()))
+    }
+
+    /// Describes every way `self` differs from the `expected` snapshot.
+    pub fn differences(&self, expected: &Snapshot) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.command != expected.command {
+            differences.push(format!(
+                "command: expected {:?}, got {:?}",
+                expected.command, self.command
+            ));
+        }
+        if self.args != expected.args {
+            differences.push(format!(
+                "args: expected {:?}, got {:?}",
+                expected.args, self.args
+            ));
+        }
+        if self.cwd != expected.cwd {
+            differences.push(format!(
+                "cwd: expected {:?}, got {:?}",
+                expected.cwd, self.cwd
+            ));
+        }
+        for (name, value) in &expected.env {
+            match self.env.get(name) {
+                None => differences.push(format!("env {}: expected {:?}, not set", name, value)),
+                Some(actual) if actual != value => differences.push(format!(
+                    "env {}: expected {:?}, got {:?}",
+                    name, value, actual
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, value) in &self.env {
+            if !expected.env.contains_key(name) {
+                differences.push(format!("env {}: not expected, got {:?}", name, value));
+            }
+        }
+        differences
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(args: &[&str], env: &[(&str, &str)]) -> Snapshot {
+        let mut command = Command::new("app");
+        command.args(args).current_dir("/srv/app");
+        for (name, value) in env {
+            command.env(name, value);
+        }
+        Snapshot::new(&command, "0123456789abcdef")
+    }
+
+    #[test]
+    fn ignores_the_launch_id() {
+        let launch = |launch_id: &str| {
+            let mut command = Command::new("app");
+            command
+                .arg(format!("--log={}.log", launch_id))
+                .env(LAUNCH_ID_VAR, launch_id);
+            Snapshot::new(&command, launch_id)
+        };
+        let first = launch("0123456789abcdef");
+        assert_eq!(first.args, ["--log={launch_id}.log"]);
+        assert!(first.env.is_empty());
+        assert!(launch("fedcba9876543210").differences(&first).is_empty());
+    }
+
+    #[test]
+    fn leaves_secrets_out() {
+        verbose::hide("snapshot-secret-value");
+        let expected = snapshot(
+            &["--token=snapshot-secret-value"],
+            &[("TOKEN", "snapshot-secret-value")],
+        );
+        assert_eq!(expected.args, ["--token={secret}"]);
+        assert_eq!(expected.env["TOKEN"], "{secret}");
+        let content = toml::to_string(&expected).unwrap();
+        assert!(!content.contains("snapshot-secret-value"), "{}", content);
+    }
+
+    #[test]
+    fn matches_itself() {
+        let expected = snapshot(&["--port", "80"], &[("MODE", "prod")]);
+        assert!(expected.differences(&expected).is_empty());
+    }
+
+    #[test]
+    fn lists_every_difference() {
+        let expected = snapshot(&["--port", "80"], &[("MODE", "prod"), ("OLD", "1")]);
+        let actual = snapshot(&["--port", "81"], &[("MODE", "dev"), ("NEW", "2")]);
+        assert_eq!(
+            actual.differences(&expected),
+            [
+                r#"args: expected ["--port", "80"], got ["--port", "81"]"#,
+                r#"env MODE: expected "prod", got "dev""#,
+                r#"env OLD: expected "1", not set"#,
+                r#"env NEW: not expected, got "2""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn survives_a_round_trip() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.toml", std::process::id()));
+        let expected = snapshot(&["a b"], &[("MODE", "prod")]);
+        expected.save(&path).unwrap();
+        let loaded = Snapshot::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.differences(&expected).is_empty());
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::Context;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// How much of the early stderr is shown, from the end.
+const KEPT: u64 = 64 * 1024;
+
+/// Numbers the files of the commands started by one launch.
+static CAPTURED: AtomicUsize = AtomicUsize::new(0);
+
+/// File in the temp directory a detached command writes its stderr to, read
+/// by the launcher so it can be shown if the command fails while starting.
+/// The file is left for a command that keeps running, which goes on writing
+/// to it after the launcher has exited.
+pub struct EarlyStderr {
+    path: PathBuf,
+}
+
+impl EarlyStderr {
+    pub fn capture(command: &mut Command) -> anyhow::Result<Self> {
+        let path = std::env::temp_dir().join(format!(
+            "env-launcher-{}-{}.stderr",
+            crate::launch_id(),
+            CAPTURED.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = File::create(&path).with_context(|| format!("create {}", path.display()))?;
+        command.stderr(file);
+        Ok(EarlyStderr { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What the command wrote so far, the last `KEPT` bytes of it, trimmed.
+    pub fn read(&self) -> String {
+        let mut kept = Vec::new();
+        if let Ok(mut file) = File::open(&self.path) {
+            let len = file.metadata().map_or(0, |metadata| metadata.len());
+            let _ = file.seek(SeekFrom::Start(len.saturating_sub(KEPT)));
+            let _ = file.read_to_end(&mut kept);
+        }
+        String::from_utf8_lossy(&kept).trim().to_string()
+    }
+
+    /// Removes the file once the command has exited.
+    pub fn remove(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Watches the child for `grace` and returns its exit status if it exited
+/// in that time.
+pub fn watch(child: &mut Child, grace: Duration) -> anyhow::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + grace;
+    loop {
+        if let Some(status) = child.try_wait().context("wait for child process")? {
+            return Ok(Some(status));
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        std::thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
@@ -0,0 +1,131 @@
+#[cfg(windows)]
+use crate::expand;
+#[cfg(windows)]
+use anyhow::Context;
+use std::ffi::OsString;
+#[cfg(windows)]
+use windows::core::{w, PCWSTR, PWSTR};
+#[cfg(windows)]
+use windows::Win32::Foundation::ERROR_NO_MORE_ITEMS;
+#[cfg(windows)]
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegEnumValueW, RegOpenKeyExW, RegQueryInfoKeyW, HKEY, HKEY_CURRENT_USER,
+    HKEY_LOCAL_MACHINE, KEY_READ, REG_EXPAND_SZ, REG_SZ, REG_VALUE_TYPE,
+};
+
+/// The environment a new logon would start with: the launcher's own with the
+/// machine and then the user variables from the registry set over it. The
+/// user's `Path` is appended to the machine's rather than replacing it.
+#[cfg(windows)]
+pub fn load() -> anyhow::Result<Vec<(OsString, OsString)>> {
+    let mut environment: Vec<(OsString, OsString)> = std::env::vars_os().collect();
+    let machine = read_key(
+        HKEY_LOCAL_MACHINE,
+        w!("SYSTEM\\CurrentControlSet\\Control\\Session Manager\\Environment"),
+    )
+    .context("read machine environment")?;
+    let user = read_key(HKEY_CURRENT_USER, w!("Environment")).context("read user environment")?;
+    let mut machine_path = None;
+    for (values, is_user) in [(machine, false), (user, true)] {
+        for (name, value, expandable) in values {
+            let mut value = if expandable {
+                expand::expand(&value, |name| get(&environment, name))
+            } else {
+                value
+            };
+            if name.eq_ignore_ascii_case("Path") {
+                if !is_user {
+                    machine_path = Some(value.clone());
+                } else if let Some(machine_path) = &machine_path {
+                    value = format!("{};{}", machine_path, value);
+                }
+            }
+            environment.retain(|(existing, _)| !existing.eq_ignore_ascii_case(&name));
+            environment.push((name.into(), value.into()));
+        }
+    }
+    Ok(environment)
+}
+
+#[cfg(not(windows))]
+pub fn load() -> anyhow::Result<Vec<(OsString, OsString)>> {
+    anyhow::bail!("reload_system_env is only supported on Windows")
+}
+
+#[cfg(windows)]
+fn get(environment: &[(OsString, OsString)], name: &str) -> Option<OsString> {
+    environment
+        .iter()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+}
+
+/// The string values of a registry key, with whether they are REG_EXPAND_SZ.
+#[cfg(windows)]
+fn read_key(root: HKEY, subkey: PCWSTR) -> anyhow::Result<Vec<(String, String, bool)>> {
+    let mut key = HKEY::default();
+    unsafe {
+        RegOpenKeyExW(root, subkey, None, KEY_READ, &mut key).ok()?;
+        let values = read_values(key);
+        let _ = RegCloseKey(key);
+        values
+    }
+}
+
+#[cfg(windows)]
+unsafe fn read_values(key: HKEY) -> anyhow::Result<Vec<(String, String, bool)>> {
+    let mut max_name_len = 0;
+    let mut max_data_len = 0;
+    RegQueryInfoKeyW(
+        key,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(&mut max_name_len),
+        Some(&mut max_data_len),
+        None,
+        None,
+    )
+    .ok()?;
+    let mut name = vec![0u16; max_name_len as usize + 1];
+    let mut data = vec![0u8; max_data_len as usize];
+    let mut values = Vec::new();
+    for index in 0.. {
+        let mut name_len = name.len() as u32;
+        let mut data_len = data.len() as u32;
+        let mut kind = 0;
+        let error = RegEnumValueW(
+            key,
+            index,
+            Some(PWSTR(name.as_mut_ptr())),
+            &mut name_len,
+            None,
+            Some(&mut kind),
+            Some(data.as_mut_ptr()),
+            Some(&mut data_len),
+        );
+        if error == ERROR_NO_MORE_ITEMS {
+            break;
+        }
+        error.ok()?;
+        let kind = REG_VALUE_TYPE(kind);
+        if kind != REG_SZ && kind != REG_EXPAND_SZ {
+            continue;
+        }
+        let wide: Vec<u16> = data[..data_len as usize]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        let value = String::from_utf16_lossy(&wide);
+        values.push((
+            String::from_utf16_lossy(&name[..name_len as usize]),
+            value.trim_end_matches('\0').to_string(),
+            kind == REG_EXPAND_SZ,
+        ));
+    }
+    Ok(values)
+}
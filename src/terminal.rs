@@ -0,0 +1,51 @@
+use crate::cmdline;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// Writes a Windows Terminal JSON fragment with a single profile running the
+/// launcher with `launcher_args`. `out` is either the fragment file or the
+/// directory to create `<name>.json` in.
+pub fn export(
+    out: &Path,
+    name: &str,
+    launcher_args: &[String],
+    icon: Option<&Path>,
+    cwd: Option<&Path>,
+) -> anyhow::Result<PathBuf> {
+    let fragment_path = if out
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+    {
+        out.to_path_buf()
+    } else {
+        out.join(name).with_extension("json")
+    };
+    let launcher_path = std::env::current_exe().context("get launcher path")?;
+    let mut profile = serde_json::json!({
+        "name": name,
+        "commandline": cmdline::join(
+            std::iter::once(launcher_path.to_string_lossy().into_owned())
+                .chain(launcher_args.iter().cloned()),
+        ),
+    });
+    if let Some(icon) = icon {
+        profile["icon"] = icon.to_string_lossy().into();
+    }
+    if let Some(cwd) = cwd {
+        profile["startingDirectory"] = cwd.to_string_lossy().into();
+    }
+    let fragment = serde_json::json!({ "profiles": [profile] });
+    if let Some(parent) = fragment_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("create directory {}", parent.display()))?;
+    }
+    std::fs::write(
+        &fragment_path,
+        serde_json::to_string_pretty(&fragment).context("serialize terminal fragment")?,
+    )
+    .with_context(|| format!("write terminal fragment {}", fragment_path.display()))?;
+    Ok(fragment_path)
+}
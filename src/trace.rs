@@ -0,0 +1,65 @@
+use crate::verbose;
+use std::path::Path;
+use tracelogging as tlg;
+
+// Listen with e.g. `tracelog -start launcher -f launcher.etl -guid *EnvLauncher`.
+tlg::define_provider!(PROVIDER, "EnvLauncher");
+
+pub fn register() {
+    // Safety: the provider is static and never unregistered before exit.
+    unsafe {
+        PROVIDER.register();
+    }
+}
+
+pub fn config_resolved(config_path: Option<&Path>, command: &Path) {
+    let config_path = config_path.map_or_else(String::new, |path| path.display().to_string());
+    verbose::log(format_args!("resolved command {}", command.display()));
+    tlg::write_event!(
+        PROVIDER,
+        "ConfigResolved",
+        level(Informational),
+        str8("ConfigPath", &config_path),
+        str8("Command", &command.display().to_string()),
+    );
+}
+
+pub fn spawn(command: &Path, pid: u32) {
+    verbose::log(format_args!(
+        "started {} as process {}",
+        command.display(),
+        pid
+    ));
+    tlg::write_event!(
+        PROVIDER,
+        "Spawn",
+        level(Informational),
+        str8("Command", &command.display().to_string()),
+        u32("Pid", &pid),
+    );
+}
+
+pub fn exit(pid: u32, exit_code: i32) {
+    verbose::log(format_args!(
+        "process {} exited with code {}",
+        pid, exit_code
+    ));
+    tlg::write_event!(
+        PROVIDER,
+        "Exit",
+        level(Informational),
+        u32("Pid", &pid),
+        i32("ExitCode", &exit_code),
+    );
+}
+
+pub fn restart(command: &Path, reason: &str) {
+    verbose::log(format_args!("restarting {}: {}", command.display(), reason));
+    tlg::write_event!(
+        PROVIDER,
+        "Restart",
+        level(Warning),
+        str8("Command", &command.display().to_string()),
+        str8("Reason", reason),
+    );
+}
@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::path::Path;
+
+/// A step applied to a variable value taken from another variable.
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Transform {
+    Trim,
+    #[serde(alias = "upper")]
+    Uppercase,
+    #[serde(alias = "lower")]
+    Lowercase,
+    Dirname,
+    Basename,
+    Replace(String, String),
+}
+
+impl Transform {
+    pub fn apply(&self, value: String) -> String {
+        match self {
+            Transform::Trim => value.trim().to_string(),
+            Transform::Uppercase => value.to_uppercase(),
+            Transform::Lowercase => value.to_lowercase(),
+            Transform::Dirname => Path::new(&value)
+                .parent()
+                .map(|parent| parent.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Transform::Basename => Path::new(&value)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Transform::Replace(from, to) => value.replace(from.as_str(), to),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use crate::error::{Coded, ErrorCode};
+use crate::{
+    build_command_with, load_config, load_document, resolved_environment, same_env_name, which,
+    CommandConfig, Config, EnvConfig,
+};
+use serde_json::Value as Schema;
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// Checks the config file at `path` more strictly than launching does and
+/// prints every problem found. Unknown keys, an empty `sep` and a command
+/// that can't be found are errors; settings without effect are warnings.
+pub fn validate(path: &Path, profile: Option<&str>, format: Option<&str>) -> anyhow::Result<()> {
+    let document = load_document(path, profile, format)?;
+    let config = load_config(path, profile, format)?;
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    let schema = serde_json::to_value(schemars::schema_for!(Config))?;
+    let mut unknown = Vec::new();
+    unknown_keys(&document, &schema, &schema, &mut Vec::new(), &mut unknown);
+    if let Some(toml::Value::Array(processes)) = document.get("processes") {
+        for (index, process) in processes.iter().enumerate() {
+            let mut process_path = vec!["processes".to_string(), format!("[{}]", index)];
+            unknown_keys(process, &schema, &schema, &mut process_path, &mut unknown);
+        }
+    }
+    // Only the file itself has spans to point at, not its includes.
+    let source = std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| toml_edit::Document::parse(content).ok());
+    for key in unknown {
+        let at = match source.as_ref().and_then(|source| locate(source, &key)) {
+            Some((line, column)) => format!("{}:{}:{}", path.display(), line, column),
+            None => path.display().to_string(),
+        };
+        errors.push(format!(
+            "{}: unknown key {}",
+            at,
+            key.join(".").replace(".[", "[")
+        ));
+    }
+
+    let configs: Vec<&Config> = if config.process_configs.is_empty() {
+        vec![&config]
+    } else {
+        config.process_configs.iter().collect()
+    };
+    let report = |problems: &mut Vec<String>, problem: String| {
+        let problem = format!("{}: {}", path.display(), problem);
+        if !problems.contains(&problem) {
+            problems.push(problem);
+        }
+    };
+    for config in configs {
+        for (name, value) in &config.env {
+            if name.is_empty() || name.contains('=') {
+                report(
+                    &mut warnings,
+                    format!("env name {:?} can't be set as it is", name),
+                );
+            }
+            if let EnvConfig::FromCommand { from_command } = value {
+                if from_command.is_empty() {
+                    report(
+                        &mut errors,
+                        format!("env.{} has an empty from_command", name),
+                    );
+                }
+            }
+            if let EnvConfig::Detailed {
+                append,
+                prepend,
+                sep,
+                dedup,
+                normalize,
+            } = value
+            {
+                if sep.as_deref() == Some("") {
+                    report(&mut errors, format!("env.{} has an empty sep", name));
+                }
+                if append.is_empty() && prepend.is_empty() && !dedup && !normalize {
+                    report(&mut warnings, format!("env.{} changes nothing", name));
+                }
+            }
+        }
+        if config.detach {
+            for (set, name) in [
+                (config.timeout.is_some(), "timeout"),
+                (config.restart.is_some(), "restart"),
+                (config.watch.is_some(), "watch"),
+            ] {
+                if set {
+                    report(
+                        &mut warnings,
+                        format!("{} has no effect with detach = true", name),
+                    );
+                }
+            }
+        }
+        if !config.detach && config.startup_grace.is_some() {
+            report(
+                &mut warnings,
+                "startup_grace has no effect without detach = true".to_string(),
+            );
+        }
+        match &config.command {
+            CommandConfig::Path(command) if command.as_os_str().is_empty() => {
+                report(&mut errors, "command not specified".to_string());
+            }
+            // Variables from vaults, http, credentials and helper commands are
+            // left out, validating must not fetch or run anything.
+            CommandConfig::Path(_) => match build_command_with(config, false) {
+                Ok(command) => {
+                    let environment = resolved_environment(config, &command);
+                    let path_var = environment
+                        .iter()
+                        .find(|(name, _)| same_env_name(name, OsStr::new("PATH")))
+                        .map(|(_, value)| value.as_os_str());
+                    let program = Path::new(command.get_program());
+                    if which::find(program, path_var).is_none() {
+                        report(
+                            &mut errors,
+                            format!("command {} not found", program.display()),
+                        );
+                    }
+                }
+                Err(error) => report(&mut errors, format!("{:#}", error)),
+            },
+            CommandConfig::App { .. } => {}
+        }
+    }
+
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    for error in &errors {
+        eprintln!("error: {}", error);
+    }
+    if !errors.is_empty() {
+        return Err(Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!("config file {} is invalid", path.display()),
+        )
+        .into());
+    }
+    eprintln!("{} is valid", path.display());
+    Ok(())
+}
+
+/// Collects the paths of the keys in `value` that `schema` doesn't know.
+/// Array items are `[index]` segments.
+fn unknown_keys(
+    value: &toml::Value,
+    schema: &Schema,
+    root: &Schema,
+    path: &mut Vec<String>,
+    unknown: &mut Vec<Vec<String>>,
+) {
+    let variants = variants(schema, root);
+    match value {
+        toml::Value::Table(table) => {
+            let mut known = serde_json::Map::new();
+            for variant in variants {
+                if !accepts(variant, "object") {
+                    continue;
+                }
+                match variant.get("additionalProperties") {
+                    None | Some(Schema::Bool(false)) => {}
+                    Some(values) => {
+                        for (key, value) in table {
+                            path.push(key.clone());
+                            unknown_keys(value, values, root, path, unknown);
+                            path.pop();
+                        }
+                        return;
+                    }
+                }
+                match variant.get("properties").and_then(Schema::as_object) {
+                    Some(properties) => known.extend(properties.clone()),
+                    // Any table will do.
+                    None => return,
+                }
+            }
+            for (key, value) in table {
+                path.push(key.clone());
+                match known.get(key) {
+                    Some(property) => unknown_keys(value, property, root, path, unknown),
+                    None if !known.is_empty() => unknown.push(path.clone()),
+                    None => {}
+                }
+                path.pop();
+            }
+        }
+        toml::Value::Array(items) => {
+            let Some(item_schema) = variants
+                .into_iter()
+                .find_map(|variant| variant.get("items"))
+            else {
+                return;
+            };
+            for (index, item) in items.iter().enumerate() {
+                path.push(format!("[{}]", index));
+                unknown_keys(item, item_schema, root, path, unknown);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The alternatives a value may match, with references resolved.
+fn variants<'a>(schema: &'a Schema, root: &'a Schema) -> Vec<&'a Schema> {
+    if let Some(reference) = schema.get("$ref").and_then(Schema::as_str) {
+        let resolved = reference
+            .strip_prefix("#/$defs/")
+            .and_then(|name| root.get("$defs")?.get(name));
+        return resolved.map_or(Vec::new(), |schema| variants(schema, root));
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(alternatives) = schema.get(keyword).and_then(Schema::as_array) {
+            return alternatives
+                .iter()
+                .flat_map(|alternative| variants(alternative, root))
+                .collect();
+        }
+    }
+    vec![schema]
+}
+
+/// Whether a value of JSON type `kind` may match `schema`.
+fn accepts(schema: &Schema, kind: &str) -> bool {
+    match schema.get("type") {
+        Some(Schema::String(name)) => name == kind,
+        Some(Schema::Array(names)) => names.iter().any(|name| name == kind),
+        _ => true,
+    }
+}
+
+/// Line and column of the key at `path` in the config file.
+fn locate(source: &toml_edit::Document<String>, path: &[String]) -> Option<(usize, usize)> {
+    let (last, parents) = path.split_last()?;
+    let mut item = source.as_item();
+    for segment in parents {
+        let index = segment
+            .strip_prefix('[')
+            .and_then(|segment| segment.strip_suffix(']'))
+            .and_then(|index| index.parse::<usize>().ok());
+        item = match index {
+            Some(index) => item.get(index)?,
+            None => item.get(segment.as_str())?,
+        };
+    }
+    let (key, _) = item.as_table_like()?.get_key_value(last)?;
+    let before = &source.raw()[..key.span()?.start];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    Some((
+        before.matches('\n').count() + 1,
+        before.len() - line_start + 1,
+    ))
+}
@@ -0,0 +1,82 @@
+use crate::http;
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct VaultSecret {
+    path: String,
+    field: String,
+}
+
+/// Vault HTTP API client configured from VAULT_ADDR and either VAULT_TOKEN
+/// or VAULT_ROLE_ID/VAULT_SECRET_ID (approle).
+pub struct VaultClient {
+    agent: ureq::Agent,
+    addr: String,
+    namespace: Option<String>,
+    token: String,
+}
+
+impl VaultClient {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let addr = std::env::var("VAULT_ADDR").context("VAULT_ADDR not set")?;
+        let mut client = VaultClient {
+            agent: http::agent(None),
+            addr: addr.trim_end_matches('/').to_string(),
+            namespace: std::env::var("VAULT_NAMESPACE").ok(),
+            token: String::new(),
+        };
+        client.token = match std::env::var("VAULT_TOKEN") {
+            Ok(token) => token,
+            Err(_) => {
+                let role_id = std::env::var("VAULT_ROLE_ID")
+                    .context("neither VAULT_TOKEN nor VAULT_ROLE_ID set")?;
+                let secret_id =
+                    std::env::var("VAULT_SECRET_ID").context("VAULT_SECRET_ID not set")?;
+                client.approle_login(&role_id, &secret_id)?
+            }
+        };
+        Ok(client)
+    }
+
+    fn approle_login(&self, role_id: &str, secret_id: &str) -> anyhow::Result<String> {
+        let url = format!("{}/v1/auth/approle/login", self.addr);
+        let mut request = self.agent.post(&url);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response: Value = request
+            .send_json(serde_json::json!({ "role_id": role_id, "secret_id": secret_id }))
+            .and_then(|mut response| response.body_mut().read_json())
+            .with_context(|| format!("vault approle login {}", url))?;
+        response
+            .pointer("/auth/client_token")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .context("vault approle login returned no client token")
+    }
+
+    pub fn read(&self, secret: &VaultSecret) -> anyhow::Result<String> {
+        let url = format!("{}/v1/{}", self.addr, secret.path.trim_start_matches('/'));
+        let mut request = self.agent.get(&url).header("X-Vault-Token", &self.token);
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+        let response: Value = request
+            .call()
+            .and_then(|mut response| response.body_mut().read_json())
+            .with_context(|| format!("read vault secret {}", secret.path))?;
+        // KV v2 nests the secret under data.data, KV v1 puts it directly under data.
+        let data = response
+            .pointer("/data/data")
+            .or_else(|| response.get("data"))
+            .with_context(|| format!("vault secret {} has no data", secret.path))?;
+        match data.get(&secret.field) {
+            Some(Value::String(value)) => Ok(value.clone()),
+            Some(value) => Ok(value.to_string()),
+            None => anyhow::bail!("vault secret {} has no field {}", secret.path, secret.field),
+        }
+    }
+}
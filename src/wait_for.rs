@@ -0,0 +1,68 @@
+use crate::error::{Coded, ErrorCode};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Conditions that must hold before the command is started.
+#[derive(Deserialize, JsonSchema)]
+pub struct WaitFor {
+    /// `host:port` that accepts TCP connections.
+    tcp: Option<String>,
+    /// File that exists, relative to the config file.
+    pub file: Option<PathBuf>,
+    /// Time waited in addition to the other conditions.
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    delay: Option<Duration>,
+    /// Time after which the launcher gives up; unlimited if not set.
+    #[serde(default, with = "humantime_serde")]
+    #[schemars(with = "Option<String>")]
+    timeout: Option<Duration>,
+}
+
+impl WaitFor {
+    /// Blocks until every condition holds, `file` being the resolved path of
+    /// the file to wait for.
+    pub fn wait(&self, file: Option<&Path>) -> anyhow::Result<()> {
+        let started = Instant::now();
+        let deadline = self.timeout.map(|timeout| started + timeout);
+        loop {
+            let pending = if let Some(delay) = self.delay.filter(|delay| started.elapsed() < *delay)
+            {
+                format!("{} delay", humantime::format_duration(delay))
+            } else if let Some(file) = file.filter(|file| !file.exists()) {
+                file.display().to_string()
+            } else if let Some(tcp) = self.tcp.as_deref().filter(|tcp| !reachable(tcp)) {
+                tcp.to_string()
+            } else {
+                return Ok(());
+            };
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Err(Coded::new(
+                    ErrorCode::Timeout,
+                    format!("timed out waiting for {}", pending),
+                )
+                .into());
+            }
+            let mut sleep = POLL_INTERVAL;
+            if let Some(deadline) = deadline {
+                sleep = sleep.min(deadline.saturating_duration_since(Instant::now()));
+            }
+            std::thread::sleep(sleep);
+        }
+    }
+}
+
+fn reachable(address: &str) -> bool {
+    let Ok(addresses) = address.to_socket_addrs() else {
+        return false;
+    };
+    addresses
+        .into_iter()
+        .any(|address| TcpStream::connect_timeout(&address, CONNECT_TIMEOUT).is_ok())
+}
@@ -0,0 +1,61 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Files whose changes restart the command.
+#[derive(Deserialize, JsonSchema)]
+pub struct WatchConfig {
+    pub paths: Vec<PathBuf>,
+}
+
+/// Remembers the modification times of watched files to notice changes.
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    pub fn new(paths: impl IntoIterator<Item = PathBuf>) -> Self {
+        let files = paths
+            .into_iter()
+            .map(|path| {
+                let modified = modified(&path);
+                (path, modified)
+            })
+            .collect();
+        Watcher { files }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The first file that was modified, created or removed since the
+    /// watcher was created.
+    pub fn changed(&self) -> Option<&Path> {
+        self.files
+            .iter()
+            .find(|(path, last)| modified(path) != *last)
+            .map(|(path, _)| path.as_path())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The command was stopped because the config file changed, for the caller
+/// to load it again and start over.
+#[derive(Debug)]
+pub struct ConfigChanged(pub PathBuf);
+
+impl fmt::Display for ConfigChanged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "config file {} changed", self.0.display())
+    }
+}
+
+impl std::error::Error for ConfigChanged {}
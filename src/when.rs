@@ -0,0 +1,138 @@
+use crate::error::{Coded, ErrorCode};
+use crate::{profile, verbose};
+use toml::Value;
+
+/// Merges every `[when.'<condition>']` table whose condition holds over the
+/// config document, in file order, like a profile.
+pub fn apply(document: &mut Value) -> anyhow::Result<()> {
+    let Some(Value::Table(blocks)) = document
+        .as_table_mut()
+        .and_then(|table| table.remove("when"))
+    else {
+        return Ok(());
+    };
+    for (condition, block) in blocks {
+        let holds = holds(&condition)?;
+        verbose::log(format_args!(
+            "when {:?} {}",
+            condition,
+            if holds { "holds" } else { "doesn't hold" }
+        ));
+        if holds {
+            profile::merge(document, block);
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates comparisons such as `os == "windows"` or `env.CI != "true"`,
+/// joined with `&&`. An unset variable equals no value.
+fn holds(condition: &str) -> anyhow::Result<bool> {
+    let invalid = || {
+        Coded::new(
+            ErrorCode::ConfigInvalid,
+            format!("invalid when condition {}", condition),
+        )
+    };
+    for comparison in condition.split("&&") {
+        let (subject, expected, equal) = match comparison.split_once("!=") {
+            Some((subject, expected)) => (subject, expected, false),
+            None => {
+                let (subject, expected) = comparison.split_once("==").ok_or_else(invalid)?;
+                (subject, expected, true)
+            }
+        };
+        let expected = expected.trim();
+        let expected = expected
+            .strip_prefix('"')
+            .and_then(|expected| expected.strip_suffix('"'))
+            .unwrap_or(expected);
+        let matches = match subject.trim() {
+            "os" => std::env::consts::OS == expected,
+            "arch" => std::env::consts::ARCH == expected,
+            "hostname" => hostname().is_some_and(|name| name.eq_ignore_ascii_case(expected)),
+            subject => {
+                let name = subject.strip_prefix("env.").ok_or_else(invalid)?;
+                std::env::var(name).is_ok_and(|value| value == expected)
+            }
+        };
+        if matches != equal {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(windows)]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return None;
+    }
+    let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+    Some(String::from_utf8_lossy(&buffer[..len]).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, holds};
+    use std::env::consts::{ARCH, OS};
+
+    #[test]
+    fn compares_os_and_arch() {
+        assert!(holds(&format!("os == \"{}\"", OS)).unwrap());
+        assert!(holds(&format!("os == {}", OS)).unwrap());
+        assert!(!holds(&format!("os != \"{}\"", OS)).unwrap());
+        assert!(holds("os != \"plan9\"").unwrap());
+        assert!(holds(&format!("os == \"{}\" && arch == \"{}\"", OS, ARCH)).unwrap());
+        assert!(!holds(&format!("os == \"{}\" && arch == \"none\"", OS)).unwrap());
+    }
+
+    #[test]
+    fn compares_variables() {
+        std::env::set_var("ENV_LAUNCHER_WHEN_TEST", "yes");
+        assert!(holds("env.ENV_LAUNCHER_WHEN_TEST == \"yes\"").unwrap());
+        assert!(!holds("env.ENV_LAUNCHER_WHEN_TEST != \"yes\"").unwrap());
+        // An unset variable matches no value, not even an empty one.
+        assert!(!holds("env.ENV_LAUNCHER_WHEN_UNSET == \"\"").unwrap());
+        assert!(holds("env.ENV_LAUNCHER_WHEN_UNSET != \"yes\"").unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed_conditions() {
+        assert!(holds("os = \"linux\"").is_err());
+        assert!(holds("user == \"me\"").is_err());
+    }
+
+    #[test]
+    fn merges_blocks_that_hold_in_file_order() {
+        let mut document: toml::Value = toml::from_str(&format!(
+            r#"
+            command = "app"
+            [env]
+            MODE = "base"
+            KEPT = "1"
+            [when.'os == "{os}"'.env]
+            MODE = "first"
+            [when.'os != "{os}"'.env]
+            MODE = "skipped"
+            OTHER = "skipped"
+            [when.'os == "{os}" && arch == "{arch}"'.env]
+            MODE = "last"
+            "#,
+            os = OS,
+            arch = ARCH
+        ))
+        .unwrap();
+        apply(&mut document).unwrap();
+        assert_eq!(document["env"]["MODE"].as_str(), Some("last"));
+        assert_eq!(document["env"]["KEPT"].as_str(), Some("1"));
+        assert!(document["env"].get("OTHER").is_none());
+        assert!(document.get("when").is_none());
+    }
+}
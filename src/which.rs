@@ -0,0 +1,50 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+/// Finds `program` as a shell would: as a path if it has a directory part,
+/// otherwise in the directories of `path`. On Windows a name without an
+/// extension is tried with each extension of `PATHEXT`.
+pub fn find(program: &Path, path: Option<&OsStr>) -> Option<PathBuf> {
+    let candidates = candidates(program);
+    if program.components().count() > 1 {
+        return candidates
+            .into_iter()
+            .find(|candidate| is_program(candidate));
+    }
+    std::env::split_paths(path?).find_map(|dir| {
+        candidates
+            .iter()
+            .map(|candidate| dir.join(candidate))
+            .find(|candidate| is_program(candidate))
+    })
+}
+
+#[cfg(windows)]
+fn is_program(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(unix)]
+fn is_program(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(windows)]
+fn candidates(program: &Path) -> Vec<PathBuf> {
+    if program.extension().is_some() {
+        return vec![program.to_path_buf()];
+    }
+    let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".into());
+    extensions
+        .split(';')
+        .filter_map(|extension| extension.strip_prefix('.'))
+        .map(|extension| program.with_extension(extension))
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn candidates(program: &Path) -> Vec<PathBuf> {
+    vec![program.to_path_buf()]
+}
@@ -0,0 +1,72 @@
+#[cfg(windows)]
+use windows::core::{BOOL, HSTRING};
+#[cfg(windows)]
+use windows::Win32::Foundation::{HWND, LPARAM, WPARAM};
+#[cfg(windows)]
+use windows::Win32::UI::WindowsAndMessaging::{
+    EnumWindows, FindWindowW, GetWindowThreadProcessId, IsIconic, IsWindowVisible, PostMessageW,
+    SetForegroundWindow, ShowWindow, SW_RESTORE, WM_CLOSE,
+};
+
+#[cfg(windows)]
+pub fn find_by_class(class: &str) -> Option<HWND> {
+    unsafe { FindWindowW(&HSTRING::from(class), None) }.ok()
+}
+
+/// Returns a visible top-level window of the process `pid`.
+#[cfg(windows)]
+pub fn find_by_pid(pid: u32) -> Option<HWND> {
+    struct Search {
+        pid: u32,
+        found: Option<HWND>,
+    }
+    unsafe extern "system" fn visit(hwnd: HWND, lparam: LPARAM) -> BOOL {
+        let search = unsafe { &mut *(lparam.0 as *mut Search) };
+        let mut pid = 0;
+        unsafe { GetWindowThreadProcessId(hwnd, Some(&mut pid)) };
+        if pid == search.pid && unsafe { IsWindowVisible(hwnd) }.as_bool() {
+            search.found = Some(hwnd);
+            return false.into();
+        }
+        true.into()
+    }
+    let mut search = Search { pid, found: None };
+    let _ = unsafe { EnumWindows(Some(visit), LPARAM(&mut search as *mut Search as isize)) };
+    search.found
+}
+
+/// Brings `hwnd` to the foreground, restoring it if it is minimized.
+#[cfg(windows)]
+pub fn focus(hwnd: HWND) {
+    unsafe {
+        if IsIconic(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+        let _ = SetForegroundWindow(hwnd);
+    }
+}
+
+/// Asks `hwnd` to close, as its close button would.
+#[cfg(windows)]
+pub fn close(hwnd: HWND) -> bool {
+    unsafe { PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0)) }.is_ok()
+}
+
+/// Windows are platform specific, none is ever found elsewhere.
+#[cfg(not(windows))]
+pub enum Window {}
+
+#[cfg(not(windows))]
+pub fn find_by_class(_class: &str) -> Option<Window> {
+    None
+}
+
+#[cfg(not(windows))]
+pub fn find_by_pid(_pid: u32) -> Option<Window> {
+    None
+}
+
+#[cfg(not(windows))]
+pub fn focus(window: Window) {
+    match window {}
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn launcher() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_env-launcher"))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("env-launcher-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn command_line_words_are_not_expanded() {
+    let dir = temp_dir("args");
+    let config = dir.join("app.toml");
+    let content = format!(
+        "command = {:?}\nargs = [\"--first={{1}}\", \"{{args}}\", \"${{X}}\"]\n[env]\nX = \"from-config\"\n",
+        env!("CARGO_BIN_EXE_env-launcher")
+    );
+    std::fs::write(&config, content).unwrap();
+    let dry_run = launcher()
+        .arg("-c")
+        .arg(&config)
+        .args(["--dry-run", "100%%", "$$", "${X}", "%X%"])
+        .output()
+        .unwrap();
+    assert!(dry_run.status.success(), "{:?}", dry_run);
+    let stdout = String::from_utf8(dry_run.stdout).unwrap();
+    let command_line = stdout.lines().next().unwrap();
+    assert!(
+        command_line.ends_with(" --first=100%% 100%% $$ ${X} %X% from-config"),
+        "{}",
+        command_line
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn env_placeholders_are_not_expanded_again() {
+    let dir = temp_dir("env-placeholder");
+    let config = dir.join("app.toml");
+    let content = format!(
+        "command = {:?}\nargs = [\"{{env:SELF}}\", \"{{env:OTHER}}-{{env:PLAIN}}\"]\n",
+        env!("CARGO_BIN_EXE_env-launcher")
+    );
+    std::fs::write(&config, content).unwrap();
+    let dry_run = launcher()
+        .arg("-c")
+        .arg(&config)
+        .arg("--dry-run")
+        .env("SELF", "{env:SELF}")
+        .env("OTHER", "{env:PLAIN}")
+        .env("PLAIN", "plain")
+        .output()
+        .unwrap();
+    assert!(dry_run.status.success(), "{:?}", dry_run);
+    let stdout = String::from_utf8(dry_run.stdout).unwrap();
+    let command_line = stdout.lines().next().unwrap();
+    assert!(
+        command_line.ends_with(" {env:SELF} {env:PLAIN}-plain"),
+        "{}",
+        command_line
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn launcher() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_env-launcher"))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("env-launcher-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn check_passes_right_after_update() {
+    let dir = temp_dir("check");
+    let config = dir.join("app.toml");
+    std::fs::write(
+        &config,
+        "command = \"app\"\nargs = [\"--log\", \"{launch_id}.log\"]\n[env]\nMODE = \"test\"\n",
+    )
+    .unwrap();
+    let snapshot = dir.join("snap.toml");
+    let update = launcher()
+        .arg("-c")
+        .arg(&config)
+        .args(["check", "--against"])
+        .arg(&snapshot)
+        .arg("--update")
+        .output()
+        .unwrap();
+    assert!(update.status.success(), "{:?}", update);
+    let check = launcher()
+        .arg("-c")
+        .arg(&config)
+        .args(["check", "--against"])
+        .arg(&snapshot)
+        .output()
+        .unwrap();
+    assert!(check.status.success(), "{:?}", check);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn launcher() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_env-launcher"))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("env-launcher-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn env_entries_build_on_env_file_values() {
+    let dir = temp_dir("env-file");
+    std::fs::write(
+        dir.join("app.env"),
+        "LIST=from-file\nLITERAL='${LIST}'\nEXPANDED=\"${LIST}\"\n",
+    )
+    .unwrap();
+    let config = dir.join("app.toml");
+    std::fs::write(
+        &config,
+        "command = \"app\"\nenv_files = [\"app.env\"]\n[env]\nLIST = { append = [\"more\"], sep = \";\" }\n",
+    )
+    .unwrap();
+    let print_env = launcher()
+        .arg("-c")
+        .arg(&config)
+        .arg("--print-env")
+        .output()
+        .unwrap();
+    assert!(print_env.status.success(), "{:?}", print_env);
+    let stdout = String::from_utf8(print_env.stdout).unwrap();
+    for expected in [
+        "LIST=from-file;more",
+        "LITERAL=${LIST}",
+        "EXPANDED=from-file",
+    ] {
+        assert!(stdout.lines().any(|line| line == expected), "{}", stdout);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
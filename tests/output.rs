@@ -0,0 +1,46 @@
+// The commands below need a Unix shell.
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+fn launcher() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_env-launcher"))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("env-launcher-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn converts_output_written_to_log_files() {
+    let dir = temp_dir("output");
+    let config = dir.join("app.toml");
+    // "hé\n" on stdout and "ok\n" on stderr, both in UTF-16.
+    std::fs::write(
+        &config,
+        concat!(
+            "command = \"sh\"\n",
+            "args = [\"-c\", \"printf 'h\\\\000\\\\351\\\\000\\\\n\\\\000'; ",
+            "printf 'o\\\\000k\\\\000\\\\n\\\\000' >&2\"]\n",
+            "output_encoding = \"utf16\"\n",
+            "stdout = \"out.log\"\n",
+            "stderr = \"err.log\"\n",
+        ),
+    )
+    .unwrap();
+    let run = launcher().arg("-c").arg(&config).output().unwrap();
+    assert!(run.status.success(), "{:?}", run);
+    assert!(run.stdout.is_empty(), "{:?}", run);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("out.log")).unwrap(),
+        "hé\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.join("err.log")).unwrap(),
+        "ok\n"
+    );
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+fn launcher() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_env-launcher"))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("env-launcher-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn validate_leaves_dynamic_values_alone() {
+    let dir = temp_dir("validate");
+    let config = dir.join("app.toml");
+    // Neither source could be resolved, so resolving them would fail.
+    let content = format!(
+        "command = {:?}\n[env]\nTOKEN = {{ from_command = [\"no-such-helper-program\"] }}\nSECRET = {{ http = \"http://127.0.0.1:9/secret\" }}\n",
+        env!("CARGO_BIN_EXE_env-launcher")
+    );
+    std::fs::write(&config, content).unwrap();
+    let validate = launcher()
+        .arg("-c")
+        .arg(&config)
+        .arg("validate")
+        .output()
+        .unwrap();
+    assert!(validate.status.success(), "{:?}", validate);
+    std::fs::remove_dir_all(&dir).unwrap();
+}